@@ -0,0 +1,242 @@
+use crate::inflate;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Archive entries are listed as "bundle.zip!inner/file.rs"
+pub const SEPARATOR: char = '!';
+
+// Entries decompressing to more than this are skipped
+pub const ENTRY_SIZE_CAP: usize = 8 * 1024 * 1024;
+
+// Largest GNU long name or pax header read from a tar
+const LONG_NAME_CAP: usize = 64 * 1024;
+
+enum Kind {
+    Zip,
+    Tar,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "zip" | "jar" => Some(Kind::Zip),
+        "tar" => Some(Kind::Tar),
+        _ => None,
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+// Split "bundle.zip!inner/file.rs" into the archive and entry paths
+pub fn split(path: &str) -> Option<(&str, &str)> {
+    let (archive, entry) = path.split_once(SEPARATOR)?;
+    is_archive(Path::new(archive)).then_some((archive, entry))
+}
+
+// Where an entry's bytes are in its archive
+#[derive(Clone, Copy)]
+enum Location {
+    Zip { header_offset: usize, compressed_size: usize, size: usize, method: usize },
+    Tar { offset: u64, size: usize },
+}
+
+// The regular files of one archive, in order, as it was when indexed
+struct Index {
+    modified: Option<SystemTime>,
+    len: u64,
+    entries: Vec<(String, Location)>,
+}
+
+// Archives indexed so far. Listing an archive indexes it, and each entry
+// read later (from any search worker) seeks straight to its bytes instead
+// of reading and parsing the archive again. An archive that changed on
+// disk is indexed afresh.
+static INDEXES: Mutex<BTreeMap<PathBuf, Index>> = Mutex::new(BTreeMap::new());
+
+fn with_index<T>(archive: &Path, f: impl FnOnce(&Index) -> T) -> io::Result<T> {
+    let meta = fs::metadata(archive)?;
+    let modified = meta.modified().ok();
+    let mut indexes = INDEXES.lock().unwrap();
+    if let Some(index) = indexes.get(archive)
+        && index.modified == modified
+        && index.len == meta.len()
+    {
+        return Ok(f(index));
+    }
+    let entries = match kind(archive) {
+        Some(Kind::Zip) => zip_entries(archive)?,
+        Some(Kind::Tar) => tar_entries(archive)?,
+        None => Vec::new(),
+    };
+    let index = Index { modified, len: meta.len(), entries };
+    let value = f(&index);
+    indexes.insert(archive.to_path_buf(), index);
+    Ok(value)
+}
+
+// Names of the regular files in an archive
+pub fn list_entries(archive: &Path) -> io::Result<Vec<String>> {
+    with_index(archive, |index| index.entries.iter().map(|(name, _)| name.clone()).collect())
+}
+
+// The bytes of one entry, at most ENTRY_SIZE_CAP of them
+pub fn read_entry(archive: &Path, name: &str) -> io::Result<Vec<u8>> {
+    let location = with_index(archive, |index| {
+        index.entries.iter().find(|(entry, _)| entry == name).map(|&(_, location)| location)
+    })?
+    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no entry {}", name)))?;
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, format!("{} is over the size cap", name));
+    let mut file = File::open(archive)?;
+    match location {
+        Location::Zip { header_offset, compressed_size, size, method } => {
+            if size > ENTRY_SIZE_CAP {
+                return Err(too_large());
+            }
+            let mut header = [0u8; 30];
+            file.seek(SeekFrom::Start(header_offset as u64))?;
+            file.read_exact(&mut header)?;
+            if &header[..4] != b"PK\x03\x04" {
+                return Err(invalid("corrupt zip entry header"));
+            }
+            let skip = u16_at(&header, 26)? + u16_at(&header, 28)?;
+            file.seek(SeekFrom::Current(skip as i64))?;
+            let compressed = read_up_to(&mut file, compressed_size)?;
+            match method {
+                0 => Ok(compressed),
+                8 => inflate::inflate(&compressed, ENTRY_SIZE_CAP).map_err(|e| invalid(&e)),
+                method => Err(invalid(&format!("unsupported zip compression method {}", method))),
+            }
+        }
+        Location::Tar { offset, size } => {
+            if size > ENTRY_SIZE_CAP {
+                return Err(too_large());
+            }
+            file.seek(SeekFrom::Start(offset))?;
+            read_up_to(&mut file, size)
+        }
+    }
+}
+
+// Exactly `size` bytes, growing the buffer as they arrive so a size taken
+// from a corrupt header can't allocate more than the file holds
+fn read_up_to(file: &mut File, size: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    file.take(size as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < size {
+        return Err(invalid("truncated archive entry"));
+    }
+    Ok(bytes)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<usize> {
+    let bytes = data.get(at..at + 2).ok_or_else(|| invalid("truncated zip"))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<usize> {
+    let bytes = data.get(at..at + 4).ok_or_else(|| invalid("truncated zip"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+// Regular files from the central directory, found through the end-of-
+// central-directory record at the end of the file (zip64 archives are not
+// read)
+fn zip_entries(archive: &Path) -> io::Result<Vec<(String, Location)>> {
+    const END_SIGNATURE: &[u8] = b"PK\x05\x06";
+    const ENTRY_SIGNATURE: &[u8] = b"PK\x01\x02";
+    let data = fs::read(archive)?;
+    let end = data
+        .windows(4)
+        .rposition(|window| window == END_SIGNATURE)
+        .ok_or_else(|| invalid("not a zip archive"))?;
+    let count = u16_at(&data, end + 10)?;
+    let mut at = u32_at(&data, end + 16)?;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if data.get(at..at + 4) != Some(ENTRY_SIGNATURE) {
+            return Err(invalid("corrupt zip central directory"));
+        }
+        let name_len = u16_at(&data, at + 28)?;
+        let name = data.get(at + 46..at + 46 + name_len).ok_or_else(|| invalid("truncated zip"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        if !name.ends_with('/') {
+            let location = Location::Zip {
+                header_offset: u32_at(&data, at + 42)?,
+                compressed_size: u32_at(&data, at + 20)?,
+                size: u32_at(&data, at + 24)?,
+                method: u16_at(&data, at + 10)?,
+            };
+            entries.push((name, location));
+        }
+        at += 46 + name_len + u16_at(&data, at + 30)? + u16_at(&data, at + 32)?;
+    }
+    Ok(entries)
+}
+
+// Each regular file in an uncompressed tar, including GNU long names and
+// pax `path` records
+fn tar_entries(archive: &Path) -> io::Result<Vec<(String, Location)>> {
+    let mut file = File::open(archive)?;
+    let mut entries = Vec::new();
+    let mut offset = 0u64;
+    let mut long_name: Option<String> = None;
+    let mut header = [0u8; 512];
+    loop {
+        if file.read_exact(&mut header).is_err() || header.iter().all(|&b| b == 0) {
+            return Ok(entries);
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let size = usize::from_str_radix(field(124..136).trim(), 8).map_err(|_| invalid("corrupt tar header"))?;
+        let data_offset = offset + 512;
+        let mut name = field(0..100);
+        if &header[257..262] == b"ustar" && header[345] != 0 {
+            name = format!("{}/{}", field(345..500), name);
+        }
+
+        match header[156] {
+            // GNU long name and pax extended headers describe the next entry
+            // A name can't need more than LONG_NAME_CAP; a bigger header is
+            // corrupt or hostile, and is skipped rather than read
+            b'L' | b'x' if size > LONG_NAME_CAP => {
+                long_name = None;
+                file.seek(SeekFrom::Current(size.next_multiple_of(512) as i64))?;
+            }
+            b'L' | b'x' => {
+                let mut data = vec![0; size];
+                file.read_exact(&mut data)?;
+                let text = String::from_utf8_lossy(&data);
+                long_name = if header[156] == b'L' {
+                    Some(text.trim_end_matches('\0').to_string())
+                } else {
+                    text.lines()
+                        .find_map(|record| record.split_once(" path=").map(|(_, path)| path.to_string()))
+                        .or(long_name)
+                };
+                file.seek(SeekFrom::Current((size.next_multiple_of(512) - size) as i64))?;
+            }
+            b'0' | 0 => {
+                entries.push((long_name.take().unwrap_or(name), Location::Tar { offset: data_offset, size }));
+                file.seek(SeekFrom::Current(size.next_multiple_of(512) as i64))?;
+            }
+            _ => {
+                long_name = None;
+                file.seek(SeekFrom::Current(size.next_multiple_of(512) as i64))?;
+            }
+        }
+        offset = data_offset + size.next_multiple_of(512) as u64;
+    }
+}
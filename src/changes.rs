@@ -0,0 +1,63 @@
+use crate::search::{Rows, SearchResult};
+use crate::tree::Tree;
+use crossterm::style::Color;
+use std::collections::HashSet;
+
+// Ctrl-N: compare the results with those of the block left behind by the
+// last Enter. A result is the same one when its path and line number are;
+// its text may have been cut differently.
+pub type Keys = HashSet<(String, usize)>;
+
+// The path of a result the last Enter's block didn't have
+pub const ADDED_COLOR: Color = Color::Green;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Off,
+    // Every result, the added ones in ADDED_COLOR
+    Mark,
+    // Only the added results
+    Only,
+}
+
+impl View {
+    pub fn next(self) -> View {
+        match self {
+            View::Off => View::Mark,
+            View::Mark => View::Only,
+            View::Only => View::Off,
+        }
+    }
+}
+
+pub fn keys(rows: &Rows, files: &[String]) -> Keys {
+    let results = match rows {
+        Rows::Results(results) => results,
+        Rows::Tree(tree) => &tree.results,
+        Rows::Listing(count) => return files[..*count].iter().map(|file| (file.clone(), 0)).collect(),
+    };
+    results.iter().map(key).collect()
+}
+
+pub fn key(result: &SearchResult) -> (String, usize) {
+    (result.path.clone(), result.line)
+}
+
+// How many results `rows` has that `baseline` doesn't, and the other way
+pub fn count(rows: &Keys, baseline: &Keys) -> (usize, usize) {
+    (rows.difference(baseline).count(), baseline.difference(rows).count())
+}
+
+// `rows` without the results `baseline` already had. A listing becomes
+// plain file rows; a tree is regrown from what is left.
+pub fn only_added(rows: Rows, files: &[String], baseline: &Keys, folded: &HashSet<String>) -> Rows {
+    let added = |result: &SearchResult| !baseline.contains(&key(result));
+    match rows {
+        Rows::Results(mut results) => {
+            results.retain(added);
+            Rows::Results(results)
+        }
+        Rows::Listing(count) => Rows::Results(files[..count].iter().map(|file| SearchResult::file(file)).filter(added).collect()),
+        Rows::Tree(tree) => Rows::Tree(Tree::new(tree.results.into_iter().filter(added).collect(), folded)),
+    }
+}
@@ -0,0 +1,210 @@
+// --generate-completions: a completion script for bash, zsh or fish,
+// written from the table of flags below. The table follows Options::parse;
+// a flag added there belongs here too.
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+// What a flag's value is, which decides what is offered after it
+enum Value {
+    // The flag takes no value
+    Nothing,
+    // Free text: nothing is offered
+    Text,
+    File,
+    Dir,
+    // One of these words, space-separated
+    OneOf(&'static str),
+}
+
+struct Flag {
+    long: &'static str,
+    short: Option<char>,
+    value: Value,
+    // May be given more than once
+    repeats: bool,
+}
+
+const fn flag(long: &'static str, value: Value) -> Flag {
+    Flag { long, short: None, value, repeats: false }
+}
+
+const fn short(long: &'static str, short: char, value: Value) -> Flag {
+    Flag { long, short: Some(short), value, repeats: false }
+}
+
+const fn repeats(long: &'static str, value: Value) -> Flag {
+    Flag { long, short: None, value, repeats: true }
+}
+
+const FLAGS: &[Flag] = &[
+    flag("unique", Value::Nothing),
+    flag("loose", Value::Nothing),
+    short("literal", 'F', Value::Nothing),
+    flag("regex", Value::Nothing),
+    flag("normalize", Value::Nothing),
+    short("line-regexp", 'x', Value::Nothing),
+    short("only-matching", 'o', Value::Nothing),
+    flag("watch", Value::Nothing),
+    flag("always-redraw", Value::Nothing),
+    flag("tree", Value::Nothing),
+    flag("in-comments", Value::Nothing),
+    flag("in-strings", Value::Nothing),
+    flag("keep-cr", Value::Nothing),
+    flag("show-config", Value::Nothing),
+    flag("list-files", Value::Nothing),
+    flag("paths", Value::Nothing),
+    flag("glob", Value::Nothing),
+    flag("fuzzy", Value::Nothing),
+    flag("path-case", Value::OneOf("insensitive sensitive smart")),
+    flag("query", Value::Text),
+    flag("pattern-file", Value::File),
+    flag("named", Value::Text),
+    flag("json", Value::Nothing),
+    flag("markdown", Value::Nothing),
+    flag("from-json", Value::Nothing),
+    flag("pager", Value::Nothing),
+    flag("stdin", Value::Nothing),
+    flag("exec-source", Value::Text),
+    flag("search-archives", Value::Nothing),
+    flag("serve", Value::File),
+    flag("format", Value::Text),
+    repeats("pattern", Value::Text),
+    repeats("not", Value::Text),
+    flag("prefilter", Value::Text),
+    flag("wrap", Value::Nothing),
+    flag("show-match-counts", Value::Nothing),
+    flag("heatmap", Value::Nothing),
+    flag("results-only", Value::Nothing),
+    flag("stats", Value::Nothing),
+    flag("show-captures", Value::Nothing),
+    flag("dim-context", Value::Nothing),
+    flag("trim", Value::Nothing),
+    flag("collapse-whitespace", Value::Nothing),
+    flag("show-age", Value::Nothing),
+    flag("show-whitespace", Value::Nothing),
+    flag("preview", Value::Nothing),
+    flag("layout", Value::OneOf("horizontal vertical")),
+    flag("highlight-style", Value::OneOf("color bold underline reverse")),
+    flag("syntax-highlight", Value::Nothing),
+    flag("extensions", Value::Text),
+    repeats("map-ext", Value::Text),
+    repeats("add-extensions", Value::Text),
+    flag("natural-sort", Value::Nothing),
+    flag("sort", Value::OneOf("walk recency path")),
+    flag("recency-weight", Value::Text),
+    flag("exec", Value::Text),
+    flag("exec-key", Value::Text),
+    flag("tracked-only", Value::Nothing),
+    flag("gitignore", Value::Nothing),
+    flag("shebang", Value::Text),
+    flag("dot-prefix", Value::Nothing),
+    flag("elide-middle", Value::Nothing),
+    flag("follow-symlinks", Value::Nothing),
+    flag("git-diff", Value::Nothing),
+    flag("binary", Value::Nothing),
+    flag("byte-offsets", Value::Nothing),
+    flag("modified-since", Value::Text),
+    flag("modified-within", Value::Text),
+    flag("min-size", Value::Text),
+    flag("max-size", Value::Text),
+    flag("empty", Value::Nothing),
+    flag("non-empty", Value::Nothing),
+    flag("timeout-ms", Value::Text),
+    flag("file-timeout-ms", Value::Text),
+    short("max-columns", 'M', Value::Text),
+    flag("window", Value::Text),
+    flag("range", Value::Text),
+    flag("context", Value::Text),
+    flag("threads", Value::Text),
+    flag("field", Value::Text),
+    flag("delimiter", Value::Text),
+    flag("record-separator", Value::Text),
+    flag("root", Value::Dir),
+    flag("query-fifo", Value::File),
+    repeats("path", Value::Dir),
+    flag("save-root", Value::Text),
+    flag("rev", Value::Text),
+    flag("generate-completions", Value::OneOf("bash zsh fish")),
+];
+
+// The script for `shell`, one of SHELLS
+pub fn script(shell: &str) -> String {
+    match shell {
+        "bash" => bash(),
+        "zsh" => zsh(),
+        _ => fish(),
+    }
+}
+
+fn names(flag: &Flag) -> Vec<String> {
+    let mut names = vec![format!("--{}", flag.long)];
+    names.extend(flag.short.map(|short| format!("-{}", short)));
+    names
+}
+
+// Completes after a flag by the flag's value, else the flag names
+fn bash() -> String {
+    let mut cases = String::new();
+    for flag in FLAGS {
+        let reply = match flag.value {
+            Value::Nothing => continue,
+            Value::Text => "return".to_string(),
+            Value::File => "COMPREPLY=($(compgen -f -- \"$cur\")); return".to_string(),
+            Value::Dir => "COMPREPLY=($(compgen -d -- \"$cur\")); return".to_string(),
+            Value::OneOf(words) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return", words),
+        };
+        cases.push_str(&format!("        {})\n            {}\n            ;;\n", names(flag).join("|"), reply));
+    }
+    let all: Vec<String> = FLAGS.iter().flat_map(names).collect();
+    format!(
+        "_termiscope() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}}
+    local prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    case \"$prev\" in
+{}    esac
+    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))
+}}
+complete -o filenames -F _termiscope termiscope
+",
+        cases,
+        all.join(" ")
+    )
+}
+
+fn zsh() -> String {
+    let mut specs = String::new();
+    for flag in FLAGS {
+        let action = match flag.value {
+            Value::Nothing => String::new(),
+            Value::Text => format!(":{}: ", flag.long),
+            Value::File => format!(":{}:_files", flag.long),
+            Value::Dir => format!(":{}:_files -/", flag.long),
+            Value::OneOf(words) => format!(":{}:({})", flag.long, words),
+        };
+        let repeat = if flag.repeats { "*" } else { "" };
+        for name in names(flag) {
+            specs.push_str(&format!(" \\\n  '{}{}{}'", repeat, name, action));
+        }
+    }
+    format!("#compdef termiscope\n\n_arguments{}\n", specs)
+}
+
+fn fish() -> String {
+    let mut lines = String::new();
+    for flag in FLAGS {
+        let mut line = format!("complete -c termiscope -l {}", flag.long);
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        match flag.value {
+            Value::Nothing => {}
+            Value::Text => line.push_str(" -x"),
+            Value::File => line.push_str(" -r -F"),
+            Value::Dir => line.push_str(" -x -a '(__fish_complete_directories)'"),
+            Value::OneOf(words) => line.push_str(&format!(" -x -a '{}'", words)),
+        }
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+    lines
+}
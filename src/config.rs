@@ -0,0 +1,205 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Settings kept in the config file: `name = value` lines under `[section]`
+// headers, with `#` starting a comment line. [bookmarks], [severity],
+// [aliases], [strings], [search] and [colors] are read.
+#[derive(Debug, Default)]
+pub struct Config {
+    // Named search roots for --root, in file order
+    pub bookmarks: Vec<(String, String)>,
+    // --pattern words and their `COLOR WEIGHT`, checked by severity.rs
+    pub severities: Vec<(String, String)>,
+    // `@name` shorthands for argument lists, in file order
+    pub aliases: Vec<(String, String)>,
+    // UI text replacing the defaults in strings.rs
+    pub strings: Vec<(String, String)>,
+    // Search defaults: `literal = true` makes queries plain text
+    pub search: Vec<(String, String)>,
+    // Interface colors replacing the defaults in theme.rs
+    pub colors: Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn bookmark(&self, name: &str) -> Option<&str> {
+        self.bookmarks
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, dir)| dir.as_str())
+    }
+
+    // The arguments `@name` stands for, split like a shell would on
+    // whitespace outside quotes. The value may be quoted as a whole:
+    // `todos = "--pattern TODO --pattern FIXME"`.
+    pub fn alias(&self, name: &str) -> Option<Vec<String>> {
+        let (_, value) = self.aliases.iter().find(|(key, _)| key == name)?;
+        Some(split_words(unquote(value)))
+    }
+
+    // Whether queries are plain text unless --regex is given; the last
+    // `literal` entry of [search] counts, and regexes are the default
+    pub fn literal(&self) -> Result<bool, String> {
+        match self.search.iter().rev().find(|(key, _)| key == "literal") {
+            None => Ok(false),
+            Some((_, value)) => match value.as_str() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                other => Err(format!("[search] literal: expected true or false, not `{}`", other)),
+            },
+        }
+    }
+}
+
+// A value quoted as a whole, `"Suche: "`, without its quotes, which keep
+// the spaces at either end that are otherwise trimmed
+pub fn unquote(value: &str) -> &str {
+    match value.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        Some(inner) if !inner.contains('"') => inner,
+        _ => value,
+    }
+}
+
+// "--query 'a b'" -> ["--query", "a b"]; quotes group, backslash escapes
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, '\\') if quote != Some('\'') => word.get_or_insert_default().extend(chars.next()),
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+const BOOKMARKS: &str = "[bookmarks]";
+const SEVERITY: &str = "[severity]";
+const ALIASES: &str = "[aliases]";
+const STRINGS: &str = "[strings]";
+const SEARCH: &str = "[search]";
+const COLORS: &str = "[colors]";
+
+// $TERMISCOPE_CONFIG, else termiscope/config under $XDG_CONFIG_HOME or ~/.config
+pub fn path() -> Option<PathBuf> {
+    if let Ok(path) = env::var("TERMISCOPE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(base.join("termiscope").join("config"))
+}
+
+// A missing config file is the same as an empty one
+pub fn load() -> Result<Config, String> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("cannot read {}: {}", path.display(), e)),
+    }
+}
+
+fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut section = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line.to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected `name = value`", index + 1));
+        };
+        let entry = (key.trim().to_string(), value.trim().to_string());
+        match section.as_str() {
+            BOOKMARKS => config.bookmarks.push(entry),
+            SEVERITY => config.severities.push(entry),
+            ALIASES => config.aliases.push(entry),
+            STRINGS => config.strings.push(entry),
+            SEARCH => config.search.push(entry),
+            COLORS => config.colors.push(entry),
+            _ => {}
+        }
+    }
+    Ok(config)
+}
+
+// Add or replace `name = dir` in the [bookmarks] section, creating the
+// file and section as needed. Other lines are kept as they are.
+pub fn save_bookmark(name: &str, dir: &str) -> Result<PathBuf, String> {
+    let path = path().ok_or("no config location: set TERMISCOPE_CONFIG or HOME")?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+    let entry = format!("{} = {}", name, dir);
+
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    match lines.iter().position(|line| line.trim() == BOOKMARKS) {
+        Some(header) => {
+            // Drop an earlier entry of the same name, keep the others
+            let mut at = header + 1;
+            while at < section_end(&lines, header) {
+                if lines[at].split_once('=').is_some_and(|(key, _)| key.trim() == name) {
+                    lines.remove(at);
+                } else {
+                    at += 1;
+                }
+            }
+            // After the section's last entry, ahead of any blank lines
+            let mut at = section_end(&lines, header);
+            while at > header + 1 && lines[at - 1].trim().is_empty() {
+                at -= 1;
+            }
+            lines.insert(at, entry);
+        }
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(BOOKMARKS.to_string());
+            lines.push(entry);
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("cannot create {}: {}", parent.display(), e))?;
+    }
+    fs::write(&path, lines.join("\n") + "\n").map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// Index of the line after the section whose header is at `header`
+fn section_end(lines: &[String], header: usize) -> usize {
+    lines[header + 1..]
+        .iter()
+        .position(|line| line.trim().starts_with('['))
+        .map_or(lines.len(), |i| header + 1 + i)
+}
+
+// "~/src" -> "$HOME/src"
+pub fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
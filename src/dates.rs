@@ -0,0 +1,101 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Parse an absolute UTC date, "YYYY-MM-DD" optionally followed by
+// "THH:MM" or "THH:MM:SS" (a space also works as the separator)
+pub fn parse_date(text: &str) -> Result<SystemTime, String> {
+    let invalid = || format!("invalid date: {} (expected YYYY-MM-DD[THH:MM[:SS]])", text);
+    let (date, time) = match text.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+
+    let fields: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = fields[..] else {
+        return Err(invalid());
+    };
+    let year: i64 = year.parse().map_err(|_| invalid())?;
+    let month: i64 = month.parse().map_err(|_| invalid())?;
+    let day: i64 = day.parse().map_err(|_| invalid())?;
+    // Four-digit years keep the arithmetic below far from overflowing
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0;
+    if let Some(time) = time {
+        let parts = time
+            .split(':')
+            .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (hours, minutes, secs) = match parts[..] {
+            [h, m] => (h, m, 0),
+            [h, m, s] => (h, m, s),
+            _ => return Err(invalid()),
+        };
+        if !(0..24).contains(&hours) || !(0..60).contains(&minutes) || !(0..60).contains(&secs) {
+            return Err(invalid());
+        }
+        seconds = hours * 3600 + minutes * 60 + secs;
+    }
+
+    let timestamp = days_from_civil(year, month, day) * 86_400 + seconds;
+    let time = if timestamp >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(timestamp as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(timestamp.unsigned_abs()))
+    };
+    time.ok_or_else(invalid)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Parse a relative duration such as "30s", "15m", "12h", "7d" or "2w"
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration: {} (expected a number followed by s, m, h, d or w)", text);
+    let split = text.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (amount, unit) = text.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => return Err(invalid()),
+    };
+    amount.checked_mul(unit_seconds).map(Duration::from_secs).ok_or_else(invalid)
+}
+
+// A duration in its largest whole unit: "45s", "12m", "3h", "5d", "6w",
+// "4mo" or "2y"
+pub fn compact(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (amount, unit) = match seconds {
+        0..60 => (seconds, "s"),
+        60..3600 => (seconds / 60, "m"),
+        3600..86_400 => (seconds / 3600, "h"),
+        86_400..1_209_600 => (seconds / 86_400, "d"),
+        1_209_600..5_184_000 => (seconds / 604_800, "w"),
+        5_184_000..31_536_000 => (seconds / 2_592_000, "mo"),
+        _ => (seconds / 31_536_000, "y"),
+    };
+    format!("{}{}", amount, unit)
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's
+// days_from_civil)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,109 @@
+// Fitting a path into the path column. By default the end is kept after
+// "..."; with --elide-middle the first directory is kept as well and the
+// middle goes, so "src/ui/widgets/list/main.rs" becomes "src/.../list/main.rs".
+// Lengths are counted in chars, so a cut never lands inside one.
+
+const ELLIPSIS: &str = "...";
+
+pub struct Elided {
+    pub text: String,
+    // The pieces of the path kept in `text`: where each starts in `text`,
+    // and its byte range in the path
+    pub kept: Vec<(usize, usize, usize)>,
+}
+
+pub fn elide(path: &str, columns: usize, middle: bool) -> Elided {
+    if path.chars().count() <= columns {
+        return Elided { text: path.to_string(), kept: vec![(0, 0, path.len())] };
+    }
+    if middle && let Some(elided) = keep_first(path, columns) {
+        return elided;
+    }
+    let tail = last_chars(path, columns.saturating_sub(ELLIPSIS.len()));
+    Elided { text: format!("{}{}", ELLIPSIS, &path[tail..]), kept: vec![(ELLIPSIS.len(), tail, path.len())] }
+}
+
+// The first directory (with a leading "./" or "/"), "...", then as many
+// whole components from the end as fit, at least the file name. None when
+// even that does not fit or there is no middle to drop.
+fn keep_first(path: &str, columns: usize) -> Option<Elided> {
+    let lead = path.len() - path.trim_start_matches("./").trim_start_matches('/').len();
+    let head = lead + path[lead..].find('/')? + 1;
+    let name = path.rfind('/')?;
+    if name < head {
+        return None;
+    }
+    let room = columns.checked_sub(path[..head].chars().count() + ELLIPSIS.len())?;
+    // Each candidate tail starts at a '/', from the file name's back towards
+    // the head; the last one that fits wins
+    let mut tail = None;
+    for (at, c) in path[head - 1..].char_indices().rev() {
+        let at = head - 1 + at;
+        if c != '/' || at < head || path[at..].chars().count() > room {
+            continue;
+        }
+        tail = Some(at);
+    }
+    let tail = tail?;
+    let text = format!("{}{}{}", &path[..head], ELLIPSIS, &path[tail..]);
+    let tail_at = head + ELLIPSIS.len();
+    Some(Elided { text, kept: vec![(0, 0, head), (tail_at, tail, path.len())] })
+}
+
+// The byte offset where the last `count` chars of `text` start
+fn last_chars(text: &str, count: usize) -> usize {
+    match count {
+        0 => text.len(),
+        _ => text.char_indices().rev().nth(count - 1).map_or(0, |(at, _)| at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits_unchanged() {
+        for middle in [false, true] {
+            let elided = elide("src/main.rs", 11, middle);
+            assert_eq!(elided.text, "src/main.rs");
+            assert_eq!(elided.kept, [(0, 0, 11)]);
+        }
+        assert_eq!(elide("", 0, false).text, "");
+    }
+
+    #[test]
+    fn keeps_the_end() {
+        let elided = elide("src/ui/widgets/list/main.rs", 12, false);
+        assert_eq!(elided.text, "...t/main.rs");
+        assert_eq!(elided.kept, [(3, 18, 27)]);
+    }
+
+    #[test]
+    fn keeps_the_first_directory() {
+        let elided = elide("src/ui/widgets/list/main.rs", 20, true);
+        assert_eq!(elided.text, "src/.../list/main.rs");
+        assert_eq!(elided.kept, [(0, 0, 4), (7, 14, 27)]);
+        assert_eq!(elide("./src/ui/widgets/main.rs", 18, true).text, "./src/.../main.rs");
+    }
+
+    #[test]
+    fn single_component() {
+        // Nothing to drop from the middle, so the end is kept
+        for middle in [false, true] {
+            assert_eq!(elide("a_rather_long_name.rs", 10, middle).text, "...name.rs");
+        }
+        assert_eq!(elide("src/a_rather_long_name.rs", 10, true).text, "...name.rs");
+    }
+
+    #[test]
+    fn multibyte_names() {
+        let path = "döcs/ünïcode/ñame.rs";
+        let elided = elide(path, 10, false);
+        assert_eq!(elided.text, "...ñame.rs");
+        assert_eq!(&path[elided.kept[0].1..], "ñame.rs");
+        let elided = elide(path, 16, true);
+        assert_eq!(elided.text, "döcs/.../ñame.rs");
+        assert_eq!(elided.text.chars().count(), 16);
+    }
+}
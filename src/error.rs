@@ -0,0 +1,39 @@
+use std::fmt;
+use std::io;
+
+// Why termiscope stopped. Everything that can fail on the way to the UI
+// (or during it) comes back to main as one of these, which puts the
+// terminal back and reports it; nothing below main prints and exits.
+#[derive(Debug)]
+pub enum Error {
+    // A bad flag, flag value or combination of flags
+    Usage(String),
+    // The config file can't be read or written, or has a bad entry
+    Config(String),
+    // Something to search can't be had: a root or search directory, a
+    // revision, the records or text on stdin
+    Input(String),
+    // --serve's socket or --query-fifo's pipe can't be set up
+    Listen(String, io::Error),
+    // Drawing to or reading from the terminal failed
+    Terminal(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) | Error::Config(message) | Error::Input(message) => write!(f, "{}", message),
+            Error::Listen(what, e) => write!(f, "cannot {}: {}", what, e),
+            Error::Terminal(e) => write!(f, "terminal error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// The UI's drawing calls fail with io::Error, all of them the terminal's
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Terminal(e)
+    }
+}
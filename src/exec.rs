@@ -0,0 +1,140 @@
+use crate::search::SearchResult;
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Expand `{}` to the result's path, `{line}` to its line number and
+// `{column}` to its match's column. Paths are single-quoted so spaces and
+// shell metacharacters in names are inert.
+pub fn expand(template: &str, result: &SearchResult) -> String {
+    template
+        .replace("{line}", &result.file_line().max(1).to_string())
+        .replace("{column}", &result.column.max(1).to_string())
+        .replace("{}", &shell_quote(&result.path))
+}
+
+pub fn run(template: &str, result: &SearchResult) -> io::Result<ExitStatus> {
+    Command::new("sh").arg("-c").arg(expand(template, result)).status()
+}
+
+// Run the template for several results at once. A template using `{+}` runs
+// once with every distinct path in its place (fzf style); any other template
+// runs once per result.
+pub fn run_all(template: &str, results: &[SearchResult]) -> io::Result<()> {
+    if template.contains("{+}") {
+        let mut paths: Vec<&str> = Vec::new();
+        for result in results {
+            if !paths.contains(&result.path.as_str()) {
+                paths.push(&result.path);
+            }
+        }
+        let quoted = paths.iter().map(|p| shell_quote(p)).collect::<Vec<_>>().join(" ");
+        Command::new("sh").arg("-c").arg(template.replace("{+}", &quoted)).status()?;
+    } else {
+        for result in results {
+            run(template, result)?;
+        }
+    }
+    Ok(())
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// --exec-source: a command's output is read up to SOURCE_CAP bytes and for
+// at most SOURCE_TIMEOUT, so a command that keeps writing (journalctl -f,
+// kubectl logs -f) gives what it wrote so far rather than hanging
+const SOURCE_CAP: u64 = 64 * 1024 * 1024;
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Output {
+    pub text: String,
+    // Why the output was cut short, if it was
+    pub cut: Option<&'static str>,
+}
+
+// Run `command` with sh and read its stdout, which is decoded leniently.
+// Its stderr is dropped so it can't scribble over the screen; a command
+// that fails without writing anything is an error. The command gets its
+// own process group, so cutting it short stops a whole pipeline and not
+// just the shell.
+pub fn capture(command: &str) -> io::Result<Output> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or_else(|| io::Error::other("no stdout"))?;
+    let (done, read) = mpsc::channel();
+    // The reader can outlive a timeout while a grandchild holds the pipe open
+    let chunks = thread::spawn(move || {
+        let mut stdout = stdout.take(SOURCE_CAP + 1);
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            match stdout.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if done.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    let deadline = std::time::Instant::now() + SOURCE_TIMEOUT;
+    let mut bytes = Vec::new();
+    let mut cut = None;
+    loop {
+        match read.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+            Ok(chunk) => bytes.extend(chunk),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                cut = Some("still running after 5 s");
+                break;
+            }
+        }
+    }
+    if bytes.len() as u64 > SOURCE_CAP {
+        bytes.truncate(SOURCE_CAP as usize);
+        cut = Some("over 64 MiB");
+    }
+    if cut.is_some() {
+        kill_group(&mut child);
+    } else {
+        let _ = chunks.join();
+    }
+    let status = child.wait()?;
+    if bytes.is_empty() && !status.success() {
+        return Err(io::Error::other(format!("exited with {}", status)));
+    }
+    Ok(Output { text: String::from_utf8_lossy(&bytes).into_owned(), cut })
+}
+
+// capture on a thread of its own; the receiver gets the one result
+pub fn capture_in_background(command: &str) -> mpsc::Receiver<io::Result<Output>> {
+    let (done, result) = mpsc::channel();
+    let command = command.to_string();
+    thread::spawn(move || done.send(capture(&command)));
+    result
+}
+
+// Kill the group `child` leads (its id is the group's, from process_group(0)),
+// falling back to the child alone if kill(1) can't be run
+fn kill_group(child: &mut Child) {
+    let group = format!("-{}", child.id());
+    let killed = Command::new("kill")
+        .args(["-KILL", "--", &group])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !killed {
+        let _ = child.kill();
+    }
+}
@@ -0,0 +1,83 @@
+// --field: the byte range of a line's Nth delimited field, CSV style. A
+// field starting with a quote runs to its closing quote, delimiters inside
+// don't split it and "" stands for a quote; the range of a quoted field is
+// inside its quotes.
+pub fn span(line: &str, n: usize, delimiter: char) -> Option<(usize, usize)> {
+    let mut index = 1;
+    let mut start = 0;
+    let mut quoted = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        if c == '"' {
+            if !quoted {
+                quoted = at == start;
+            } else if chars.peek().is_some_and(|&(_, next)| next == '"') {
+                chars.next();
+            } else {
+                quoted = false;
+            }
+        } else if c == delimiter && !quoted {
+            if index == n {
+                return Some(unquoted(line, start, at));
+            }
+            index += 1;
+            start = at + c.len_utf8();
+        }
+    }
+    (index == n).then(|| unquoted(line, start, line.len()))
+}
+
+fn unquoted(line: &str, start: usize, end: usize) -> (usize, usize) {
+    let field = &line[start..end];
+    if field.len() >= 2 && field.starts_with('"') && field.ends_with('"') {
+        (start + 1, end - 1)
+    } else {
+        (start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(line: &str, n: usize) -> Option<&str> {
+        span(line, n, ',').map(|(start, end)| &line[start..end])
+    }
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(field("a,b,c", 1), Some("a"));
+        assert_eq!(field("a,b,c", 2), Some("b"));
+        assert_eq!(field("a,b,c", 3), Some("c"));
+        assert_eq!(span("a;b", 2, ';'), Some((2, 3)));
+    }
+
+    #[test]
+    fn quoted_delimiters() {
+        assert_eq!(field(r#"x,"a,b",y"#, 2), Some("a,b"));
+        assert_eq!(field(r#"x,"a,b",y"#, 3), Some("y"));
+        assert_eq!(field(r#""say ""hi"", ok",z"#, 1), Some(r#"say ""hi"", ok"#));
+        // A quote inside a field doesn't start quoting
+        assert_eq!(field(r#"a"b,c"#, 2), Some("c"));
+    }
+
+    #[test]
+    fn past_the_end() {
+        assert_eq!(field("a,b", 3), None);
+        assert_eq!(field("", 2), None);
+        assert_eq!(field("a,b", 0), None);
+    }
+
+    #[test]
+    fn empty_fields() {
+        assert_eq!(field("a,,c", 2), Some(""));
+        assert_eq!(field("a,b,", 3), Some(""));
+        assert_eq!(field("", 1), Some(""));
+        assert_eq!(field(r#"a,"",c"#, 2), Some(""));
+    }
+
+    #[test]
+    fn multibyte_text() {
+        assert_eq!(field("é,ü,ß", 2), Some("ü"));
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::fs::FileTypeExt;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// --query-fifo: another process (an editor plugin, a script) writes
+// queries to a named pipe, one per line, and each replaces the typed
+// query. A reader thread hands them over; when a writer closes the pipe
+// it is opened again for the next one.
+pub fn listen(path: &str) -> io::Result<Receiver<String>> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => {}
+        Ok(_) => return Err(io::Error::other(format!("{} exists and is not a named pipe", path))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => make_fifo(path)?,
+        Err(e) => return Err(e),
+    }
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_string();
+    thread::spawn(move || {
+        // Opening blocks until a writer connects
+        while let Ok(pipe) = File::open(&path) {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                let line = line.strip_suffix('\r').unwrap_or(&line).to_string();
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+// std has no mkfifo, so ask the command of that name
+fn make_fifo(path: &str) -> io::Result<()> {
+    let output = Command::new("mkfifo").arg(path).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    Ok(())
+}
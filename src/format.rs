@@ -0,0 +1,80 @@
+use crate::search::SearchResult;
+
+// One piece of a --format template such as "{path}:{line}: {text}"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part {
+    Literal(String),
+    Path,
+    Line,
+    Column,
+    Text,
+}
+
+// Split a template into literals and fields. Unknown `{...}` placeholders
+// are kept as literal text.
+pub fn parse(template: &str) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        literal.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let field = match rest.find('}').map(|close| &rest[..=close]) {
+            Some("{path}") => Some(Part::Path),
+            Some("{line}") => Some(Part::Line),
+            Some("{column}") => Some(Part::Column),
+            Some("{text}") => Some(Part::Text),
+            _ => None,
+        };
+        match field {
+            Some(field) => {
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                rest = &rest[rest.find('}').unwrap_or(0) + 1..];
+                parts.push(field);
+            }
+            None => {
+                literal.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    parts
+}
+
+// The template filled in with a result's fields, without colors
+pub fn render(parts: &[Part], result: &SearchResult) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Literal(literal) => literal.clone(),
+            Part::Path => result.path.clone(),
+            Part::Line => line_field(result),
+            Part::Column => column_field(result),
+            Part::Text => result.text.clone(),
+        })
+        .collect()
+}
+
+// File-only rows (line 0) leave {line} empty
+pub fn line_field(result: &SearchResult) -> String {
+    if result.line == 0 {
+        String::new()
+    } else {
+        result.line.to_string()
+    }
+}
+
+// As is {column}
+pub fn column_field(result: &SearchResult) -> String {
+    if result.line == 0 {
+        String::new()
+    } else {
+        result.column.to_string()
+    }
+}
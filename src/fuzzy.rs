@@ -0,0 +1,170 @@
+// --fuzzy path matching: the query's characters have to appear in the path
+// in order, not necessarily together, and paths are ranked the way fzf's
+// path scheme does: a match in the file name beats one spread over the
+// directories, a name that is the query beats one that merely contains it,
+// and each directory level costs a little, so `main` lists src/main.rs
+// ahead of vendor/x/y/mains.rs.
+
+// Per matched character that directly follows the previous one
+const CONSECUTIVE: i64 = 8;
+// Per matched character that starts a word: after / _ - . or a space, or
+// an upper case letter after a lower case one
+const WORD_START: i64 = 6;
+// Per unmatched character between the first and last match
+const GAP: i64 = 1;
+// The whole query matched within the file name
+const IN_NAME: i64 = 40;
+// The file name starts with the query
+const NAME_PREFIX: i64 = 25;
+// The file name without its extension is the query
+const EXACT_NAME: i64 = 50;
+// Per directory level of the path
+const DEPTH: i64 = 4;
+
+// The score of `path` for `query`, higher being better, or None if the
+// query's characters don't all appear in order
+pub fn score(query: &str, path: &str, ignore_case: bool) -> Option<i64> {
+    // Char by char, so positions line up with the original path
+    let fold = |text: &str| -> Vec<char> {
+        text.chars()
+            .map(|c| if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c })
+            .collect()
+    };
+    let query = fold(query);
+    let path_chars = fold(path);
+    if query.is_empty() {
+        return Some(0);
+    }
+    let name_start = path_chars.iter().rposition(|&c| c == '/').map_or(0, |slash| slash + 1);
+    let name = &path_chars[name_start..];
+
+    // Prefer a match inside the file name, else take one anywhere
+    let (positions, in_name) = match positions(&query, name) {
+        Some(positions) => (positions.into_iter().map(|at| at + name_start).collect(), true),
+        None => (positions(&query, &path_chars)?, false),
+    };
+
+    let original: Vec<char> = path.chars().collect();
+    let mut score = 0;
+    for (index, &at) in positions.iter().enumerate() {
+        if index > 0 && positions[index - 1] + 1 == at {
+            score += CONSECUTIVE;
+        }
+        if is_word_start(&original, at) {
+            score += WORD_START;
+        }
+    }
+    let span = positions[positions.len() - 1] + 1 - positions[0];
+    score -= GAP * (span - positions.len()) as i64;
+    if in_name {
+        score += IN_NAME;
+        if name.starts_with(&query) {
+            score += NAME_PREFIX;
+        }
+        let stem_len = name.iter().rposition(|&c| c == '.').filter(|&dot| dot > 0).unwrap_or(name.len());
+        if name[..stem_len] == query[..] {
+            score += EXACT_NAME;
+        }
+    }
+    score -= DEPTH * path_chars.iter().filter(|&&c| c == '/').count() as i64;
+    Some(score)
+}
+
+// Where the query's characters fall in `text`: the forward scan finds the
+// earliest place the whole query has matched, then a backward scan from
+// there finds the latest start, which gives the tightest match ending at
+// that place (as fzf's first algorithm does)
+fn positions(query: &[char], text: &[char]) -> Option<Vec<usize>> {
+    let mut next = 0;
+    let mut end = None;
+    for (at, &c) in text.iter().enumerate() {
+        if c == query[next] {
+            next += 1;
+            if next == query.len() {
+                end = Some(at);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    let mut positions = Vec::with_capacity(query.len());
+    let mut wanted = query.iter().rev().peekable();
+    for at in (0..=end).rev() {
+        if wanted.peek() == Some(&&text[at]) {
+            positions.push(at);
+            wanted.next();
+            if wanted.peek().is_none() {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+fn is_word_start(path: &[char], at: usize) -> bool {
+    let Some(&before) = at.checked_sub(1).and_then(|index| path.get(index)) else {
+        return true;
+    };
+    let c = path.get(at).copied().unwrap_or(' ');
+    matches!(before, '/' | '_' | '-' | '.' | ' ') || (before.is_lowercase() && c.is_uppercase())
+}
+
+// A query with spaces matches path components instead: each word has to
+// match one component as above, the words in order and each in a later
+// component than the last, so `src main` finds src/bin/main.rs. Of the
+// ways to place the words, the best scoring one counts.
+
+// Per component passed over between two matched ones
+const SKIPPED_COMPONENT: i64 = 3;
+// The last word matched the file name
+const LAST_IN_NAME: i64 = 30;
+
+// The score of `path` for the words of `query`, and the byte range of
+// each component they matched, or None if they can't all be placed
+pub fn score_components(query: &str, path: &str, ignore_case: bool) -> Option<(i64, Vec<(usize, usize)>)> {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let mut components = Vec::new();
+    let mut start = 0;
+    for component in path.split('/') {
+        components.push((start, component));
+        start += component.len() + 1;
+    }
+    if words.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    // best[word][component]: the best score with that word placed in that
+    // component, and where the word before it went
+    let mut best: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; components.len()]; words.len()];
+    for (index, word) in words.iter().enumerate() {
+        for at in index..components.len() {
+            let Some(score) = score(word, components[at].1, ignore_case) else {
+                continue;
+            };
+            best[index][at] = if index == 0 {
+                Some((score, 0))
+            } else {
+                (index - 1..at)
+                    .filter_map(|before| best[index - 1][before].map(|(total, _)| (total, before)))
+                    .map(|(total, before)| (total - SKIPPED_COMPONENT * (at - before - 1) as i64 + score, before))
+                    .max_by_key(|&(total, _)| total)
+            };
+        }
+    }
+
+    let last = components.len() - 1;
+    let (mut total, mut at) = (0..components.len())
+        .filter_map(|at| best[words.len() - 1][at].map(|(total, _)| (total + if at == last { LAST_IN_NAME } else { 0 }, at)))
+        .max_by_key(|&(total, _)| total)?;
+    total -= DEPTH * last as i64;
+
+    let mut ranges = Vec::with_capacity(words.len());
+    for index in (0..words.len()).rev() {
+        let (start, component) = components[at];
+        ranges.push((start, start + component.len()));
+        at = best[index][at].map_or(0, |(_, before)| before);
+    }
+    ranges.reverse();
+    Some((total, ranges))
+}
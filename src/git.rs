@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::process::Command;
+
+fn run(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// All file paths in the tree of `rev`, relative to the repository root
+pub fn list_files(rev: &str) -> io::Result<Vec<String>> {
+    let listing = run(&["ls-tree", "-r", "--name-only", "--full-tree", rev])?;
+    Ok(listing.lines().map(|line| line.to_string()).collect())
+}
+
+// Contents of `path` as of `rev`
+pub fn read_file(rev: &str, path: &str) -> io::Result<String> {
+    run(&["show", &format!("{}:{}", rev, path)])
+}
+
+// Files tracked in the index, relative to the current directory
+pub fn tracked_files() -> io::Result<Vec<String>> {
+    let listing = run(&["ls-files"])?;
+    Ok(listing.lines().map(|line| line.to_string()).collect())
+}
+
+// Untracked paths under `dir` that git ignores, relative to `dir`, with a
+// whole ignored directory given once with a trailing slash. Git applies
+// .gitignore files, .git/info/exclude and core.excludesFile in its own
+// order of precedence, and tracked files are never ignored. Paths are
+// NUL-separated so git doesn't quote unusual ones (core.quotePath).
+pub fn ignored_paths(dir: &str) -> io::Result<Vec<String>> {
+    let listing = run(&["-C", dir, "ls-files", "-z", "--others", "--ignored", "--exclude-standard", "--directory"])?;
+    Ok(listing.split_terminator('\0').map(|path| path.to_string()).collect())
+}
+
+// Lines of each file that differ from HEAD, as ranges of 1-based line
+// numbers in the working tree version, keyed by path relative to the
+// current directory
+pub type ChangedLines = HashMap<String, Vec<Range<usize>>>;
+
+const WHOLE_FILE: Range<usize> = 1..usize::MAX;
+
+// Added and changed lines from `git diff HEAD`, plus untracked files, which
+// count as changed throughout
+pub fn changed_lines() -> io::Result<ChangedLines> {
+    let diff = run(&["diff", "--unified=0", "--no-color", "--no-ext-diff", "--relative", "HEAD", "--"])?;
+    let mut changed = ChangedLines::new();
+    let mut path: Option<String> = None;
+    let mut lines = diff.lines();
+    while let Some(line) = lines.next() {
+        if let Some(new_path) = line.strip_prefix("+++ ") {
+            // "+++ /dev/null" for a deleted file
+            path = new_path.strip_prefix("b/").map(str::to_string);
+        } else if let Some(hunk) = line.strip_prefix("@@ ")
+            && let Some(path) = &path
+        {
+            // "@@ -12,3 +14,5 @@": 3 old lines replaced by 5 new ones from line 14
+            let count = |spec: &str| -> (usize, usize) {
+                match spec.split_once(',') {
+                    Some((start, count)) => (start.parse().unwrap_or(0), count.parse().unwrap_or(0)),
+                    None => (spec.parse().unwrap_or(0), 1),
+                }
+            };
+            let mut specs = hunk.split(' ');
+            let (_, removed) = specs.next().and_then(|spec| spec.strip_prefix('-')).map_or((0, 0), count);
+            let (start, added) = specs.next().and_then(|spec| spec.strip_prefix('+')).map_or((0, 0), count);
+            if added > 0 {
+                changed.entry(path.clone()).or_default().push(start..start + added);
+            }
+            // Skip the hunk body so lines that look like headers can't confuse us
+            lines.by_ref().take(removed + added).for_each(drop);
+        }
+    }
+    for path in run(&["ls-files", "--others", "--exclude-standard"])?.lines() {
+        changed.insert(path.to_string(), vec![WHOLE_FILE]);
+    }
+    Ok(changed)
+}
@@ -0,0 +1,201 @@
+// Translate a shell-style glob into an anchored regex pattern.
+//
+// `*` and `?` never cross a `/`, `**` does, `[...]` and `{a,b}` behave as in
+// most shells. Everything else is matched literally, as are a `[` or `{`
+// that is never closed. None when a class holds a range out of order
+// (`[z-a]`), which no shell matches anything with either.
+pub fn to_regex(glob: &str) -> Option<String> {
+    let chars: Vec<char> = glob.chars().collect();
+    let braces = paired_braces(&chars);
+    let mut pattern = String::from("^");
+    let mut in_braces = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    pattern.push_str("(?:.*/)?");
+                    i += 2;
+                } else {
+                    pattern.push_str(".*");
+                    i += 1;
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => match class_end(&chars, i) {
+                Some(end) => {
+                    pattern.push_str(&class(&chars[i + 1..end])?);
+                    i = end;
+                }
+                None => pattern.push_str("\\["),
+            },
+            '{' if braces[i] => {
+                in_braces += 1;
+                pattern.push_str("(?:");
+            }
+            '}' if braces[i] => {
+                in_braces -= 1;
+                pattern.push(')');
+            }
+            ',' if in_braces > 0 => pattern.push('|'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    pattern.push('$');
+    Some(pattern)
+}
+
+// Which `{` and `}` close each other; the rest are literal
+fn paired_braces(chars: &[char]) -> Vec<bool> {
+    let mut paired = vec![false; chars.len()];
+    let mut open = Vec::new();
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '{' => open.push(i),
+            '}' => {
+                if let Some(start) = open.pop() {
+                    paired[start] = true;
+                    paired[i] = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    paired
+}
+
+// The `]` closing the class opened at `start`. A `]` right after the `[`
+// (or `[!`) is a member, as in shells, so `[]]` matches "]".
+fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut first = start + 1;
+    if chars.get(first) == Some(&'!') {
+        first += 1;
+    }
+    chars[(first + 1).min(chars.len())..]
+        .iter()
+        .position(|&c| c == ']')
+        .map(|at| first + 1 + at)
+}
+
+// A class's members as a regex class. Only `-` between two members makes a
+// range; anything else the regex syntax gives a meaning to (`\`, `[`, `]`,
+// `^`, and `&&`, `--`, `~~`) is escaped.
+fn class(members: &[char]) -> Option<String> {
+    let (negated, members) = match members.split_first() {
+        Some(('!', rest)) => (true, rest),
+        _ => (false, members),
+    };
+    let mut class = String::from(if negated { "[^" } else { "[" });
+    let mut i = 0;
+    while i < members.len() {
+        let c = members[i];
+        if let (Some('-'), Some(&to)) = (members.get(i + 1), members.get(i + 2))
+            && c != '-'
+            && to != '-'
+        {
+            if to < c {
+                return None;
+            }
+            class.push_str(&format!("{}-{}", escape_member(c), escape_member(to)));
+            i += 3;
+            continue;
+        }
+        class.push_str(&escape_member(c));
+        i += 1;
+    }
+    class.push(']');
+    Some(class)
+}
+
+fn escape_member(c: char) -> String {
+    match c {
+        '\\' | '[' | ']' | '^' | '-' | '&' | '~' => format!("\\{}", c),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        Regex::new(&to_regex(glob).unwrap()).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn star_stays_in_one_component() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/*", "src/main.rs"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("src/**/*.rs", "src/main.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/main.rs"));
+        assert!(matches("**", "a/b/c"));
+        assert!(!matches("src/**/*.rs", "lib/main.rs"));
+    }
+
+    #[test]
+    fn question_mark_is_one_char() {
+        assert!(matches("?.md", "a.md"));
+        assert!(matches("?.md", "é.md"));
+        assert!(!matches("?.md", "ab.md"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(!matches("[abc].txt", "d.txt"));
+        assert!(matches("v[0-9]", "v7"));
+        assert!(!matches("v[0-9]", "vx"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn negated_classes() {
+        assert!(matches("[!abc].txt", "d.txt"));
+        assert!(!matches("[!abc].txt", "a.txt"));
+        assert!(matches("[!]]", "a"));
+        assert!(!matches("[!]]", "]"));
+    }
+
+    #[test]
+    fn alternatives() {
+        assert!(matches("*.{rs,toml}", "Cargo.toml"));
+        assert!(matches("*.{rs,toml}", "main.rs"));
+        assert!(!matches("*.{rs,toml}", "main.c"));
+        assert!(matches("{src,tests}/{a,b{c,d}}.rs", "tests/bd.rs"));
+        // Outside braces a comma is itself
+        assert!(matches("a,b", "a,b"));
+    }
+
+    #[test]
+    fn regex_class_syntax_is_literal() {
+        assert!(matches("[a&&b]", "&"));
+        assert!(matches("[a--b]", "-"));
+        assert!(!matches("[a--b]", "c"));
+        assert!(matches("[~~]", "~"));
+        assert!(matches("[[a]", "["));
+        assert!(matches("[\\]", "\\"));
+        assert!(matches("[a^]", "^"));
+    }
+
+    #[test]
+    fn malformed_globs() {
+        // Unclosed `[` and `{`, and an empty `[]`, are literal text
+        assert!(matches("[]", "[]"));
+        assert!(matches("a[b", "a[b"));
+        assert!(matches("{a,b", "{a,b"));
+        assert!(matches("a}", "a}"));
+        assert!(matches("{a,b}}", "a}"));
+        // A range out of order is rejected
+        assert_eq!(to_regex("[z-a]"), None);
+    }
+}
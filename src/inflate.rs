@@ -0,0 +1,293 @@
+// A raw DEFLATE (RFC 1951) decoder for the compressed entries of zip
+// archives, in the style of zlib's puff: canonical Huffman codes decoded a
+// bit at a time. Output beyond `limit` bytes is an error rather than a
+// memory blow-up.
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn need(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or("compressed data ends early")?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+}
+
+// Code counts per bit length and the symbols in canonical code order
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= bits.need(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// Order the code length code lengths are stored in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut bits = Bits { data, pos: 0, buffer: 0, count: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.need(1)? == 1;
+        match bits.need(2)? {
+            0 => stored(&mut bits, &mut out)?,
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                codes(&mut bits, &mut out, &lengths, &distances, limit)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, &lengths, &distances, limit)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if out.len() > limit {
+            return Err(format!("entry is larger than {} bytes", limit));
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>) -> Result<(), String> {
+    // Stored blocks start on a byte boundary
+    bits.buffer = 0;
+    bits.count = 0;
+    let header = bits.data.get(bits.pos..bits.pos + 4).ok_or("compressed data ends early")?;
+    let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+    let complement = u16::from_le_bytes([header[2], header[3]]) as usize;
+    if length != !complement & 0xffff {
+        return Err("corrupt stored block".to_string());
+    }
+    bits.pos += 4;
+    let block = bits.data.get(bits.pos..bits.pos + length).ok_or("compressed data ends early")?;
+    out.extend_from_slice(block);
+    bits.pos += length;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.need(5)? as usize + 257;
+    let distance_count = bits.need(5)? as usize + 1;
+    let code_length_count = bits.need(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.need(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(bits)?;
+        if symbol < 16 {
+            lengths[index] = symbol as u8;
+            index += 1;
+            continue;
+        }
+        let (value, repeat) = match symbol {
+            16 => {
+                let previous = *lengths[..index].last().ok_or("repeat with no previous length")?;
+                (previous, 3 + bits.need(2)? as usize)
+            }
+            17 => (0, 3 + bits.need(3)? as usize),
+            _ => (0, 11 + bits.need(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err("too many code lengths".to_string());
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= LENGTH_BASE.len() {
+                    return Err("invalid length code".to_string());
+                }
+                let length = LENGTH_BASE[symbol] as usize + bits.need(LENGTH_EXTRA[symbol])? as usize;
+                let symbol = distances.decode(bits)? as usize;
+                if symbol >= DISTANCE_BASE.len() {
+                    return Err("invalid distance code".to_string());
+                }
+                let distance = DISTANCE_BASE[symbol] as usize + bits.need(DISTANCE_EXTRA[symbol])? as usize;
+                if distance > out.len() {
+                    return Err("distance reaches before the start".to_string());
+                }
+                // Copies may overlap their own output, so go byte by byte
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+        if out.len() > limit {
+            return Err(format!("entry is larger than {} bytes", limit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inflate;
+
+    // Raw DEFLATE streams from zlib (wbits -15), one per block type; the
+    // low bits of the first byte give the type
+    const STORED: &[u8] = &[
+        0x01, 0x13, 0x00, 0xec, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x73, 0x74, 0x6f, 0x72, 0x65, 0x64, 0x20,
+        0x62, 0x6c, 0x6f, 0x63, 0x6b,
+    ];
+    const FIXED: &[u8] = &[0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x22, 0xd3, 0x32, 0x2b, 0x52, 0x53, 0x00];
+    const DYNAMIC: &[u8] = &[
+        0xed, 0xd0, 0xbb, 0x0d, 0x80, 0x30, 0x0c, 0x05, 0xc0, 0x9e, 0x29, 0xde, 0x08, 0xfc, 0x0b, 0xb6, 0x49, 0x42, 0x22,
+        0xa2, 0x18, 0x5b, 0x20, 0x10, 0xb0, 0x3d, 0x62, 0x04, 0xd7, 0xb8, 0xbf, 0xea, 0x28, 0x73, 0x44, 0x0d, 0x49, 0x70,
+        0x98, 0x1f, 0x76, 0x6b, 0x0e, 0xf0, 0x24, 0xa1, 0x4c, 0x38, 0x96, 0x88, 0xed, 0xcc, 0xa1, 0xc0, 0xef, 0x72, 0x31,
+        0x92, 0xdc, 0x15, 0x7d, 0xbc, 0xd1, 0xf1, 0x56, 0xc7, 0x3b, 0x1d, 0xef, 0x75, 0x7c, 0xd0, 0xf1, 0x51, 0xc7, 0x2d,
+        0xd2, 0x22, 0x2d, 0xd2, 0x22, 0x2d, 0xf2, 0xef, 0x91, 0x2f,
+    ];
+
+    fn dynamic_text() -> Vec<u8> {
+        (0..40).flat_map(|i| format!("line {} of a dynamic block: the quick brown fox\n", i % 7).into_bytes()).collect()
+    }
+
+    // `data` as stored blocks of at most `block` bytes, the last one final
+    fn store(data: &[u8], block: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let chunks: Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(block).collect() };
+        for (index, chunk) in chunks.iter().enumerate() {
+            out.push((index + 1 == chunks.len()) as u8);
+            let len = chunk.len() as u16;
+            out.extend(len.to_le_bytes());
+            out.extend((!len).to_le_bytes());
+            out.extend(*chunk);
+        }
+        out
+    }
+
+    #[test]
+    fn stored_block() {
+        assert_eq!(inflate(STORED, 1024).unwrap(), b"hello, stored block");
+    }
+
+    #[test]
+    fn fixed_block() {
+        assert_eq!(inflate(FIXED, 1024).unwrap(), b"hello hello hello fixed");
+    }
+
+    #[test]
+    fn dynamic_block() {
+        assert_eq!(inflate(DYNAMIC, 4096).unwrap(), dynamic_text());
+    }
+
+    #[test]
+    fn stored_round_trip() {
+        let text = dynamic_text();
+        for block in [1, 7, 100, 65535] {
+            assert_eq!(inflate(&store(&text, block), text.len()).unwrap(), text);
+        }
+        assert_eq!(inflate(&store(b"", 1), 0).unwrap(), b"");
+    }
+
+    #[test]
+    fn output_over_limit() {
+        assert!(inflate(DYNAMIC, 100).is_err());
+        assert!(inflate(STORED, 5).is_err());
+    }
+
+    #[test]
+    fn truncated_input() {
+        assert!(inflate(&DYNAMIC[..DYNAMIC.len() / 2], 4096).is_err());
+        assert!(inflate(&STORED[..10], 1024).is_err());
+        assert!(inflate(&[], 1024).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+// The search core: collecting files, matching them, and the formats
+// results are read and written in. The termiscope binary drives it from the
+// terminal, a pipe or a --serve socket.
+
+use options::Options;
+use std::path::Path;
+
+pub mod archive;
+pub mod changes;
+pub mod clipboard;
+pub mod completions;
+pub mod config;
+pub mod dates;
+pub mod elide;
+pub mod error;
+pub mod exec;
+pub mod fields;
+pub mod fifo;
+pub mod format;
+pub mod fuzzy;
+pub mod git;
+pub mod glob;
+pub mod inflate;
+pub mod json;
+pub mod keys;
+pub mod loose;
+pub mod natural;
+pub mod nfc;
+pub mod options;
+pub mod output;
+pub mod patterns;
+pub mod picker;
+pub mod query;
+pub mod preview;
+pub mod rank;
+pub mod search;
+pub mod serve;
+pub mod severity;
+pub mod strings;
+pub mod syntax;
+pub mod theme;
+pub mod tree;
+pub mod watch;
+pub mod whitespace;
+
+// The longest prefix of `text` that is at most `columns` chars wide
+pub fn clip(text: &str, columns: usize) -> &str {
+    match text.char_indices().nth(columns) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "rs", "py", "js", "ts", "html", "css", "json", "yaml", "yml", "toml", "ini", "sh",
+    "bash", "cpp", "c", "h", "java", "go", "rb", "php", "sql",
+];
+
+// --extensions replaces the default list (including TERMISCOPE_EXTENSIONS),
+// --add-extensions extends whichever list is in effect
+pub fn text_extensions(options: &Options) -> Vec<String> {
+    let mut extensions = options.extensions.clone().unwrap_or_else(|| {
+        TEXT_EXTENSIONS
+            .iter()
+            .map(|ext| ext.to_string())
+            .chain(options.env_extensions.iter().cloned())
+            .collect()
+    });
+    extensions.extend(options.add_extensions.iter().cloned());
+    // A mapped extension is searched exactly when the one it maps to is
+    let targets = extensions.clone();
+    extensions.retain(|ext| options.mapped_extension(ext) == *ext);
+    for (from, _) in &options.ext_map {
+        if targets.contains(&options.mapped_extension(from)) && !extensions.contains(from) {
+            extensions.push(from.clone());
+        }
+    }
+    extensions
+}
+
+pub fn is_text_file(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.contains(&ext.to_lowercase()))
+        .unwrap_or(false)
+}
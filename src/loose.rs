@@ -0,0 +1,49 @@
+// --loose matching: lines and queries are compared after dropping
+// apostrophes and collapsing every other run of punctuation and whitespace
+// to one space, so "cant" finds "can't" and "foo bar" finds "foo-bar".
+pub struct Normalized {
+    pub text: String,
+    // For each byte of `text`, the span of the original text it came from
+    spans: Vec<(usize, usize)>,
+}
+
+impl Normalized {
+    // The original byte range behind `text[start..end]`
+    pub fn original(&self, start: usize, end: usize) -> (usize, usize) {
+        (self.spans[start].0, self.spans[end - 1].1)
+    }
+}
+
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '’'
+}
+
+pub fn normalize(text: &str) -> Normalized {
+    let mut normalized = Normalized {
+        text: String::with_capacity(text.len()),
+        spans: Vec::with_capacity(text.len()),
+    };
+    // Start of the separator run being collapsed, if in one
+    let mut gap: Option<(usize, usize)> = None;
+    for (index, c) in text.char_indices() {
+        let end = index + c.len_utf8();
+        if is_apostrophe(c) {
+            continue;
+        }
+        if !c.is_alphanumeric() {
+            gap = Some((gap.map_or(index, |(start, _)| start), end));
+            continue;
+        }
+        if let Some(span) = gap.take() {
+            normalized.text.push(' ');
+            normalized.spans.push(span);
+        }
+        normalized.text.push(c);
+        normalized.spans.extend(std::iter::repeat_n((index, end), c.len_utf8()));
+    }
+    if let Some(span) = gap {
+        normalized.text.push(' ');
+        normalized.spans.push(span);
+    }
+    normalized
+}
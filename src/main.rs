@@ -1,20 +1,185 @@
 use crossterm::{
-    cursor::{MoveTo, Show},
-    event::{poll, read, Event, KeyCode, KeyEvent},
-    style::{Color, Print, SetForegroundColor, ResetColor},
-    terminal::{self, Clear, ClearType, size},
+    cursor::{Hide, MoveTo, Show},
+    event::{poll, read, Event, KeyCode, KeyModifiers},
+    style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor, ResetColor},
+    terminal::{self, Clear, ClearType, ScrollUp, size},
     ExecutableCommand,
 };
 use lru::LruCache;
-use regex::RegexBuilder;
+use regex::Regex;
+use termiscope::{
+    archive, changes, clipboard, completions, config, dates, elide, error, exec, fifo, format, git, natural, options,
+    output, picker, preview, rank, search, serve, severity, strings, theme, tree, watch, whitespace,
+    clip, is_text_file, text_extensions,
+};
+use error::Error;
+use options::{HighlightStyle, Layout, Options, SortOrder, DEFAULT_EXEC_KEY};
+use search::{
+    lists_all_files, load_content, search_file_contents, search_records, MatchRange, Rows, SearchResult, SearchStats,
+};
+use tree::Tree;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::io::{stdout, Write};
+use std::io::{stdout, BufRead, IsTerminal, Read, Stdout, Write};
 use std::num::NonZeroUsize;
 use std::path::Path;
-use std::time::Duration;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::{WalkDir, DirEntry};
 
-fn main() -> std::io::Result<()> {
+fn main() {
+    // A panic in the UI would otherwise leave the shell in raw mode with
+    // no cursor, and its message scattered across the screen
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+    if let Err(e) = run() {
+        restore_terminal();
+        eprintln!("termiscope: {}", e);
+        std::process::exit(EXIT_ERROR);
+    }
+}
+
+// Leave raw mode and show the cursor again, on a fresh line, if the UI
+// had taken the terminal
+fn restore_terminal() {
+    if terminal::is_raw_mode_enabled().unwrap_or(false) {
+        let _ = terminal::disable_raw_mode();
+        let _ = stdout().execute(Show);
+        eprintln!();
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let mut options = Options::from_args().map_err(Error::Usage)?;
+
+    if let Some(shell) = &options.generate_completions {
+        print!("{}", completions::script(shell));
+        return Ok(());
+    }
+
+    if let Some(name) = &options.save_root {
+        let dir = std::env::current_dir()
+            .map_err(|e| Error::Input(format!("cannot read the current directory: {}", e)))?
+            .display()
+            .to_string();
+        let path = config::save_bookmark(name, &dir).map_err(Error::Config)?;
+        println!("saved {} = {} in {}", name, dir, path.display());
+        return Ok(());
+    }
+    if let Some(root) = &options.root {
+        enter_root(root)?;
+    }
+
+    // Without --literal or --regex, the config file picks the default. A
+    // bad config file only costs that default here; the UI, which needs
+    // the file, reports it as an error.
+    if !options.literal && !options.regex {
+        match config::load().and_then(|config| config.literal()) {
+            Ok(literal) => options.literal = literal,
+            Err(e) => eprintln!("termiscope: {} ([search] ignored)", e),
+        }
+    }
+
+    // Outside a git repository --git-diff restricts nothing, like --tracked-only
+    if options.git_diff {
+        options.changed_lines = git::changed_lines().ok();
+    }
+
+    if !options.patterns.is_empty() {
+        options.severities = config::load()
+            .and_then(|config| severity::for_patterns(&options.patterns, &config))
+            .map_err(Error::Config)?;
+    }
+
+    let extensions = text_extensions(&options);
+    if options.show_config {
+        let config = config::load().map_err(Error::Config)?;
+        options.preview_focus = theme::preview_focus(&config).map_err(Error::Config)?;
+        options.ext_colors = theme::ext_colors(&config).map_err(Error::Config)?;
+        let severities = severity::table(&config).map_err(Error::Config)?;
+        show_config(&options, &extensions, &severities);
+        return Ok(());
+    }
+
+    // With --from-json the records on stdin are searched instead of files
+    let records = if options.from_json {
+        Some(output::read_json(std::io::stdin().lock()).map_err(Error::Input)?)
+    } else {
+        None
+    };
+    // With --stdin the piped text is searched as a single document
+    let stdin_text = if options.stdin {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| Error::Input(format!("cannot read stdin: {}", e)))?;
+        Some(text)
+    } else {
+        None
+    };
+    // With --exec-source a command's output is, much the same way
+    let mut source_note = None;
+    let document = match (stdin_text, &options.exec_source) {
+        (Some(text), _) => Some((STDIN_PATH, text)),
+        (None, Some(command)) => {
+            let output = exec::capture(command).map_err(|e| Error::Input(format!("cannot run {}: {}", command, e)))?;
+            source_note = output.cut.map(|cut| format!("output cut: {}", cut));
+            Some((SOURCE_PATH, output.text))
+        }
+        (None, None) => None,
+    };
+    let mut files = match (&records, &document) {
+        (Some(_), _) => Vec::new(),
+        (None, Some((path, _))) => vec![path.to_string()],
+        (None, None) => collect_files(&extensions, &options).map_err(Error::Input)?,
+    };
+
+    if options.list_files {
+        for file in &files {
+            println!("{}", file);
+        }
+        return Ok(());
+    }
+    // A walk that finds nothing would otherwise only show up as searches
+    // that never match
+    if records.is_none() && document.is_none() && files.is_empty() {
+        source_note = Some(no_files_note(&extensions, &options));
+    }
+    // Without the UI the note goes to stderr
+    if let Some(note) = &source_note
+        && (options.query.is_some() || options.serve.is_some())
+    {
+        eprintln!("termiscope: {}", note);
+    }
+
+    let mut content_cache = new_content_cache(document);
+
+    if let Some(query) = &options.query {
+        std::process::exit(run_batch(&files, records.as_deref(), &mut content_cache, query, &options));
+    }
+
+    if let Some(socket) = &options.serve {
+        let reload = || collect_files(&extensions, &options);
+        return serve::run(socket, files, &mut content_cache, &options, &reload)
+            .map_err(|e| Error::Listen(format!("serve on {}", socket), e));
+    }
+
+    let config = config::load().map_err(Error::Config)?;
+    let strings = strings::from_config(&config).map_err(Error::Config)?;
+    options.preview_focus = theme::preview_focus(&config).map_err(Error::Config)?;
+    options.ext_colors = theme::ext_colors(&config).map_err(Error::Config)?;
+    let prompt_width = strings.prompt.chars().count();
+    let fifo_queries = match &options.query_fifo {
+        Some(path) => Some(fifo::listen(path).map_err(|e| Error::Listen(format!("read queries from {}", path), e))?),
+        None => None,
+    };
+
     // Enable raw mode to capture key events
     terminal::enable_raw_mode()?;
     let mut stdout = stdout();
@@ -22,145 +187,1734 @@ fn main() -> std::io::Result<()> {
     // Clear the terminal initially
     stdout.execute(Clear(ClearType::All))?.execute(MoveTo(0, 0))?;
 
-    let mut query = String::new();
-    let files = collect_text_files();
-    let mut content_cache = LruCache::new(NonZeroUsize::new(100).expect("Cache size must be non-zero"));
-    let mut current_results: Vec<(String, String, Vec<(usize, usize)>)> = Vec::new();
-    let mut results_start_row = 2;
+    let mut query = options.initial_query.clone().unwrap_or_default();
+    let mut current_results = Rows::Results(Vec::new());
+    // The query and width `current_results` were searched with. A pass with
+    // the same ones skips the search; anything else that changes what a
+    // search would find (a reload, --watch, Enter) clears it.
+    let mut searched: Option<(String, usize)> = None;
+    // The query doesn't compile (yet): the results of the last one that
+    // did stay on screen, dimmed, and the prompt says why
+    let mut regex_error = false;
+    let mut stats = SearchStats::default();
+    let mut prompt_row = 0;
+    // --results-only (or Ctrl-F) hides the prompt and gives the results
+    // the whole screen until `/`
+    let mut results_only = options.results_only;
+    let mut results_start_row = if results_only { 0 } else { 2 };
+    let mut rendered_rows = 0;
+    // Prompt row, query and results of each block left behind by Enter,
+    // for Ctrl-Z and Ctrl-N
+    let mut committed: Vec<(u16, String, changes::Keys)> = Vec::new();
+    // Ctrl-N: the results compared with the last Enter's block. While it
+    // is on, the keys of all the current results (before View::Only
+    // drops any) and how many were added and removed.
+    let mut changes_view = changes::View::Off;
+    let mut current_keys: Option<changes::Keys> = None;
+    let mut change_counts = (0, 0);
+    let mut wrap = options.wrap;
+    let mut needs_redraw = false;
+    // A note from the start, e.g. --exec-source output being cut short
+    let mut status: Option<String> = source_note;
+    let mut search_cancelled = false;
+    let mut pending_events: VecDeque<Event> = VecDeque::new();
+    let mut selected = 0;
+    let mut offset = 0;
+    let mut marked: HashSet<usize> = HashSet::new();
+    // With several --path roots, results are tagged with their root's
+    // label until Ctrl-R hides the tags
+    let mut show_root_labels = options.search_roots.len() > 1;
+    let root_labels = root_labels(&options.search_roots);
+    // --tree: directories and files folded with Left, by path
+    let mut folded: HashSet<String> = HashSet::new();
+    let mut visible_results = 0;
+    // Preview context, changed at runtime with Alt-+ and Alt--
+    let mut context = options.context;
+    let mut preview_rows = 0;
+    let exec_key = options.exec_key.unwrap_or(DEFAULT_EXEC_KEY);
+    let row_format = options.format.as_deref().map(format::parse);
     let (terminal_width, terminal_height) = size()?;
     let terminal_width = terminal_width as usize;
+    // Ctrl-P shows and hides the preview for the rest of the session
+    let mut show_preview = options.preview;
+    // The Ctrl-O directory browser, while it is open
+    let mut picker: Option<picker::Picker> = None;
+    let mut watcher = options.watch.then(|| watch::Watcher::new(&files));
+    // --watch with --exec-source runs the command again every SOURCE_INTERVAL.
+    // F5 and Ctrl-L report the run when it ends, --watch doesn't.
+    let mut source_run = Instant::now();
+    let mut source_job: Option<SourceJob> = None;
+    // --show-age: each file's modification time, read when it is first
+    // shown and forgotten on a reload or when --watch sees it change
+    let mut modified_times: HashMap<String, Option<SystemTime>> = HashMap::new();
+    // The file name being typed after Ctrl-S, while the export prompt is open
+    let mut export_path: Option<String> = None;
 
     // Initial prompt
-    stdout
-        .execute(MoveTo(0, 0))?
-        .execute(Print("Search: "))?;
+    if results_only {
+        stdout.execute(Hide)?;
+    } else {
+        stdout.execute(MoveTo(0, 0))?.execute(Print(&strings.prompt))?;
+    }
     stdout.flush()?;
 
     loop {
+        // The directory browser takes over the results region and the keys
+        // until a directory is picked or Esc closes it
+        if let Some(browser) = &mut picker {
+            let region_rows = terminal_height.saturating_sub(results_start_row + 1) as usize;
+            if needs_redraw {
+                let area = preview::Area { x: 0, y: results_start_row, width: terminal_width, height: region_rows };
+                picker::draw(&mut stdout, &area, browser, &strings.picker_help)?;
+                stdout.flush()?;
+                needs_redraw = false;
+            }
+            let event = match pending_events.pop_front() {
+                Some(event) => Some(event),
+                None if poll(Duration::from_millis(100))? => Some(read()?),
+                None => None,
+            };
+            if let Some(Event::Key(key)) = event {
+                needs_redraw = true;
+                match key.code {
+                    KeyCode::Esc => picker = None,
+                    KeyCode::Up => browser.up(),
+                    KeyCode::Down => browser.down(),
+                    KeyCode::Right => browser.descend(),
+                    KeyCode::Left => browser.ascend(),
+                    KeyCode::Enter => {
+                        let dir = browser.chosen();
+                        picker = None;
+                        match std::env::set_current_dir(&dir) {
+                            Ok(()) => {
+                                // The picked directory replaces any --path roots
+                                options.search_roots.clear();
+                                reload(&mut files, &mut content_cache, &mut status, &mut source_job, &extensions, &mut options);
+                                modified_times.clear();
+                                marked.clear();
+                                searched = None;
+                            }
+                            Err(e) => status = Some(format!("cannot enter {}: {}", dir.display(), e)),
+                        }
+                    }
+                    _ => needs_redraw = false,
+                }
+            }
+            continue;
+        }
+
+        // The export prompt on the bottom row takes the keys until Enter
+        // writes the results or Esc closes it
+        if let Some(path) = &mut export_path {
+            let prompt = format!("{}{}", strings.export_prompt, path);
+            stdout
+                .execute(MoveTo(0, terminal_height - 1))?
+                .execute(Print(format!("{:<width$}", clip(&prompt, terminal_width - 1), width = terminal_width - 1)))?
+                .execute(MoveTo(prompt.chars().count().min(terminal_width - 1) as u16, terminal_height - 1))?;
+            stdout.flush()?;
+            let event = match pending_events.pop_front() {
+                Some(event) => Some(event),
+                None if poll(Duration::from_millis(100))? => Some(read()?),
+                None => None,
+            };
+            if let Some(Event::Key(key)) = event {
+                match key.code {
+                    KeyCode::Esc => export_path = None,
+                    KeyCode::Enter => {
+                        let path = export_path.take().unwrap_or_default();
+                        if !path.is_empty() {
+                            let results = full_results(&files, records.as_deref(), &mut content_cache, &query, &options);
+                            status = Some(match export(&path, &results, &options) {
+                                Ok(()) => format!("exported {} to {}", results.len(), path),
+                                Err(e) => format!("cannot write {}: {}", path, e),
+                            });
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        path.pop();
+                    }
+                    KeyCode::Char(c) => path.push(c),
+                    _ => {}
+                }
+                if export_path.is_none() {
+                    stdout
+                        .execute(MoveTo(0, terminal_height - 1))?
+                        .execute(Print(" ".repeat(terminal_width)))?;
+                }
+            }
+            continue;
+        }
+
+        // A query from --query-fifo replaces the typed one; of several
+        // waiting, only the last is searched
+        if let Some(queries) = &fifo_queries
+            && let Some(latest) = queries.try_iter().last()
+        {
+            query = latest;
+            status = None;
+            search_cancelled = false;
+        }
+
         // Update query display and position cursor at end of query
+        if !results_only {
+            stdout
+                .execute(MoveTo(prompt_width as u16, prompt_row))? // After the prompt
+                .execute(Print(&query))?
+                .execute(Print(" ".repeat(terminal_width.saturating_sub(prompt_width + query.len()))))? // Clear leftover text and labels
+                .execute(MoveTo((prompt_width + query.len()) as u16, prompt_row))?; // Move cursor to end of query
+        }
+
+        // With a horizontal preview the result list takes the left half and the
+        // preview the right, split by a separator column. A vertical preview
+        // goes below the list instead and leaves it the full width.
+        let side_preview = show_preview && options.layout == Layout::Horizontal;
+        let list_width = if side_preview { terminal_width / 2 } else { terminal_width };
+        // The last column of the list is reserved for the scrollbar
+        let results_width = list_width.saturating_sub(1);
+        // Room for the match count badge when it is enabled
+        let text_width = if options.show_match_counts {
+            results_width.saturating_sub(MATCH_COUNT_WIDTH)
+        } else {
+            results_width
+        };
+        // Too narrow for the path and text side by side: each result is
+        // stacked instead, the path on one row and the text on the next
+        let stacked = results_width < NARROW_WIDTH;
+
+        // Update results if changed. Wrapped rows show the whole line, so the
+        // search only truncates when wrapping is off.
+        // The search cuts text 33 columns short of this for the path beside
+        // it, then adds up to 6 for "..." marks; a stacked row only needs
+        // room for those marks
+        let search_width = if wrap {
+            usize::MAX
+        } else if stacked {
+            text_width.saturating_sub(STACK_INDENT.len() + 6) + 33
+        } else {
+            text_width
+        };
+        // After Esc cancels a search, keep its partial results instead of
+        // starting it over until the next key. --always-redraw searches and
+        // draws on every pass, for profiling.
+        let search_inputs = (query.clone(), search_width);
+        let new_results = if search_cancelled || (searched.as_ref() == Some(&search_inputs) && !options.always_redraw) {
+            None
+        } else if records.is_none() && !options.tree && lists_all_files(&query, &options) {
+            // Nothing to search: rows are drawn straight from the file list
+            searched = Some(search_inputs);
+            stats = SearchStats::default();
+            regex_error = false;
+            Some(Rows::Listing(files.len()))
+        } else {
+            searched = Some(search_inputs);
+            stats = SearchStats::default();
+            let watch = KeyWatch::new();
+            let results = match &records {
+                Some(records) => search_records(records, &query, search_width, &options),
+                None => search_file_contents(&files, &query, &mut content_cache, search_width, &options, &mut stats, &|| {
+                    watch.check()
+                }),
+            };
+            pending_events.extend(watch.pending.into_inner().unwrap());
+            if watch.cancelled.into_inner() {
+                search_cancelled = true;
+                searched = None;
+                pending_events.clear();
+                status = Some(strings.cancelled.clone());
+            }
+            if results.first().is_some_and(SearchResult::is_invalid_regex) {
+                // Mid-pattern (an open group, a trailing backslash) is no
+                // reason to throw away the list; it is searched again once
+                // the pattern compiles
+                if !regex_error {
+                    regex_error = true;
+                    needs_redraw = true;
+                }
+                None
+            } else if options.tree {
+                regex_error = false;
+                Some(Rows::Tree(Tree::new(results, &folded)))
+            } else {
+                regex_error = false;
+                Some(Rows::Results(results))
+            }
+        };
+
+        // The end of the prompt row shows the last status message until the
+        // next key, else flags results cut short by --timeout-ms, else the
+        // --stats readout
+        let readout;
+        let label = match &status {
+            Some(message) => message.as_str(),
+            None if regex_error => strings.invalid_regex.as_str(),
+            None if changes_view != changes::View::Off => {
+                readout = format!("+{} -{} since Enter", change_counts.0, change_counts.1);
+                readout.as_str()
+            }
+            None if stats.partial => strings.partial.as_str(),
+            None if options.stats && stats.files > 0 => {
+                readout = stats_readout(&stats);
+                readout.as_str()
+            }
+            None => "",
+        };
+        // A longer message (e.g. a path) widens the label; a narrow
+        // terminal shrinks it rather than cover the query. Without the
+        // prompt it goes at the end of the bottom row.
+        let label_width = STATUS_WIDTH.max(label.chars().count());
+        let (label_row, query_end) = if results_only {
+            (terminal_height - 1, 0)
+        } else {
+            (prompt_row, prompt_width + query.len() + 1)
+        };
+        let status_column = results_width.saturating_sub(label_width).max(query_end);
+        let status_width = results_width.saturating_sub(status_column);
         stdout
-            .execute(MoveTo(8, 0))? // After "Search: "
-            .execute(Print(&query))?
-            .execute(Print(" ".repeat(50)))? // Clear leftover text
-            .execute(MoveTo(8 + query.len() as u16, 0))?; // Move cursor to end of query
-
-        // Update results if changed
-        let new_results = search_file_contents(&files, &query, &mut content_cache, terminal_width);
-        if new_results != current_results {
-            current_results = new_results;
-
-            // Clear results area
-            for i in 0..(terminal_height - 3) {
+            .execute(MoveTo(status_column as u16, label_row))?
+            .execute(SetForegroundColor(if regex_error && status.is_none() { Color::Red } else { Color::DarkYellow }))?
+            .execute(Print(format!("{:>width$}", clip(label, status_width), width = status_width)))?
+            .execute(ResetColor)?
+            .execute(MoveTo((prompt_width + query.len()) as u16, prompt_row))?;
+
+        if new_results.is_some() || needs_redraw {
+            // Selection, scroll and marks belong to the set of matches, not to
+            // how they are displayed: a display-only change (e.g. toggling
+            // wrap re-truncates the text) keeps them, a different set of
+            // matches resets them to the top
+            if let Some(mut new_results) = new_results {
+                current_keys = None;
+                if changes_view != changes::View::Off
+                    && let Some((_, _, baseline)) = committed.last()
+                {
+                    let keys = changes::keys(&new_results, &files);
+                    change_counts = changes::count(&keys, baseline);
+                    if changes_view == changes::View::Only {
+                        new_results = changes::only_added(new_results, &files, baseline, &folded);
+                    }
+                    current_keys = Some(keys);
+                }
+                if !new_results.same_matches(&current_results) {
+                    selected = 0;
+                    offset = 0;
+                    marked.clear();
+                }
+                current_results = new_results;
+            }
+            needs_redraw = false;
+
+            // Scroll so the selection stays inside the window
+            if selected < offset {
+                offset = selected;
+            } else if selected >= offset + visible_results.max(1) {
+                offset = selected + 1 - visible_results.max(1);
+            }
+
+            // Clear results area, which runs to the second-to-last row so
+            // one blank line is left at the bottom
+            let region_rows = terminal_height.saturating_sub(results_start_row + 1) as usize;
+            for i in 0..region_rows as u16 {
                 stdout
-                    .execute(MoveTo(0, results_start_row + i as u16))?
+                    .execute(MoveTo(0, results_start_row + i))?
                     .execute(Print(" ".repeat(terminal_width)))?;
             }
 
-            // Display results (limited to terminal_height - 3)
-            for (i, (file, matched_str, match_ranges)) in current_results
-                .iter()
-                .take((terminal_height - 3) as usize)
-                .enumerate()
-            {
-                // Handle invalid regex
-                if file.is_empty() && matched_str == "Invalid regex pattern" {
-                    stdout
-                        .execute(MoveTo(0, results_start_row + i as u16))?
-                        .execute(SetForegroundColor(Color::Red))?
-                        .execute(Print(matched_str))?
-                        .execute(ResetColor)?;
+            // A vertical preview takes the lower half of the region, below a
+            // separator row
+            let max_rows = if show_preview && !side_preview {
+                region_rows / 2
+            } else {
+                region_rows
+            };
+
+            // The path column is as wide as the longest path on screen when
+            // the texts beside the paths leave room for it, and otherwise the
+            // usual 30 columns (or half the list); widths are counted in chars
+            // like the columns they take
+            let mut longest_path = 0;
+            let mut longest_text = 0;
+            for index in offset..current_results.len().min(offset + max_rows) {
+                if let Some(result) = current_results.get(index, &files) {
+                    let shown_path = match split_root(&result.path, &options.search_roots).filter(|_| show_root_labels) {
+                        Some((root, rest)) => root_labels[root].chars().count() + 1 + rest.chars().count(),
+                        None => result.path.chars().count(),
+                    };
+                    longest_path = longest_path.max(shown_path);
+                    let age = if options.show_age { age_badge(&result.path, &mut modified_times) } else { String::new() };
+                    let badges = age.chars().count()
+                        + captures_badge(&result.captures).chars().count()
+                        + indent_badge(result.indent).chars().count();
+                    longest_text = longest_text.max(badges + result.text.chars().count());
+                }
+            }
+            let path_column = longest_path
+                .min(text_width.saturating_sub(longest_text + 1))
+                .max(30.min(results_width / 2));
+
+            // Display results (limited to max_rows rows, which may hold
+            // fewer results when long lines wrap)
+            let mut row = 0;
+            visible_results = 0;
+            for index in offset..current_results.len() {
+                // A stacked result needs both of its rows
+                if row >= max_rows || (stacked && row + 1 >= max_rows) {
+                    break;
+                }
+                let Some(result) = current_results.get(index, &files) else {
+                    break;
+                };
+                let result = result.as_ref();
+                visible_results += 1;
+                let SearchResult { path: file, text: matched_str, ranges: match_ranges, .. } = result;
+
+                // A tree row has its own layout, whatever the width
+                if let Rows::Tree(tree) = &current_results {
+                    stdout.execute(MoveTo(0, results_start_row + row as u16))?;
+                    if regex_error {
+                        print_stale_row(&mut stdout, &output::tree_row_text(tree, &tree.rows[index]), index == selected, results_width)?;
+                        row += 1;
+                        continue;
+                    }
+                    if marked.contains(&index) {
+                        stdout.execute(Print(MARK_GLYPH))?;
+                    }
+                    let width = results_width.saturating_sub(if marked.contains(&index) { MARK_GLYPH.len() } else { 0 });
+                    print_tree_row(&mut stdout, tree, &tree.rows[index], index == selected, width, &options)?;
+                    row += 1;
                     continue;
                 }
 
-                // Truncate file path (max 30 chars)
-                let max_file_len = 30.min(terminal_width / 2);
-                let display_file = if file.len() > max_file_len {
-                    format!("...{}", &file[file.len().saturating_sub(max_file_len - 3)..])
+                // Truncate file path to its column, or the row when stacked
+                let max_file_len = if stacked {
+                    results_width.saturating_sub(MARK_GLYPH.len()).max(4)
                 } else {
-                    file.to_string()
+                    path_column
+                };
+                // With several --path roots, a tag in the root's color names
+                // the root and the path is shown from there. A long root
+                // name gives way to the path.
+                let tag = split_root(file, &options.search_roots).filter(|_| show_root_labels);
+                let (tag_text, shown_path) = match tag {
+                    Some((root, rest)) => (format!("{} ", clip(&root_labels[root], (max_file_len / 3).max(4))), rest),
+                    None => (String::new(), file.as_str()),
                 };
+                let max_path_len = max_file_len.saturating_sub(tag_text.len()).max(4);
+                let elided = elide::elide(shown_path, max_path_len, options.elide_middle);
+                let mut display_file = elided.text.clone();
+                display_file.insert_str(0, &tag_text);
+                let mut tag_start = 0;
+                if marked.contains(&index) {
+                    display_file.insert_str(0, MARK_GLYPH);
+                    tag_start = MARK_GLYPH.len();
+                }
+                let tag_end = tag_start + tag_text.len();
 
-                // Render file path
+                // Rows kept from the last query that compiled are drawn in
+                // grey, without highlights, until the new one does
+                if regex_error {
+                    stdout.execute(MoveTo(0, results_start_row + row as u16))?;
+                    let text = match &row_format {
+                        Some(parts) => format!("{}{}", if marked.contains(&index) { MARK_GLYPH } else { "" }, format::render(parts, result)),
+                        None => format!("{:<width$} {}", display_file, matched_str, width = path_column),
+                    };
+                    print_stale_row(&mut stdout, &text, index == selected, text_width)?;
+                    row += 1;
+                    continue;
+                }
+
+                // A --format template replaces the path + right-aligned text layout
+                if let Some(parts) = &row_format {
+                    stdout.execute(MoveTo(0, results_start_row + row as u16))?;
+                    print_formatted(&mut stdout, parts, result, &display_file, index == selected, text_width, &options)?;
+                    print_match_count(&mut stdout, match_ranges, options.show_match_counts)?;
+                    row += 1;
+                    continue;
+                }
+
+                // Render file path, colored by file type (or as added since
+                // the last Enter, with Ctrl-N) and reversed when selected
+                stdout.execute(MoveTo(0, results_start_row + row as u16))?;
+                if index == selected {
+                    stdout.execute(SetAttribute(Attribute::Reverse))?;
+                }
+                let added = changes_view != changes::View::Off
+                    && committed.last().is_some_and(|(_, _, baseline)| !baseline.contains(&changes::key(result)));
+                let color = if added { changes::ADDED_COLOR } else { path_color(file, &options) };
+                let tag_color = tag.map_or(color, |(root, _)| ROOT_COLORS[root % ROOT_COLORS.len()]);
                 stdout
-                    .execute(MoveTo(0, results_start_row + i as u16))?
-                    .execute(SetForegroundColor(Color::White))?
-                    .execute(Print(&display_file))?
-                    .execute(ResetColor)?;
+                    .execute(SetForegroundColor(color))?
+                    .execute(Print(&display_file[..tag_start]))?
+                    .execute(SetForegroundColor(tag_color))?
+                    .execute(Print(&display_file[tag_start..tag_end]))?;
+                print_path(&mut stdout, &elided, shown_path, result, color, &options)?;
+                stdout.execute(SetAttribute(Attribute::Reset))?.execute(ResetColor)?;
 
-                // Calculate padding
-                let padding = terminal_width.saturating_sub(display_file.len() + matched_str.len());
-                stdout.execute(Print(" ".repeat(padding)))?;
+                // With --trim, a grey badge before the text notes the dropped
+                // indent; --show-captures puts the captured values ahead of it,
+                // and --show-age the file's age ahead of those
+                let age = if options.show_age { age_badge(file, &mut modified_times) } else { String::new() };
+                let captures = captures_badge(&result.captures);
+                let badge = indent_badge(result.indent);
+                let badge_len = age.chars().count() + captures.chars().count() + badge.chars().count();
 
-                // Render matched string
-                let mut last_pos = 0;
-                for &(start, end) in match_ranges {
-                    if start > last_pos {
+                // Stacked, the text goes on the row below the path, after a
+                // short indent and left-aligned
+                let (text_start, gap) = if stacked {
+                    row += 1;
+                    stdout
+                        .execute(MoveTo(0, results_start_row + row as u16))?
+                        .execute(Print(STACK_INDENT))?;
+                    (STACK_INDENT.len(), 0)
+                } else {
+                    (display_file.chars().count(), 1)
+                };
+
+                if !wrap || text_start + gap + badge_len + matched_str.len() <= text_width {
+                    // Calculate padding
+                    if !stacked {
+                        let padding = text_width.saturating_sub(display_file.chars().count() + badge_len + matched_str.chars().count());
+                        stdout.execute(Print(" ".repeat(padding)))?;
+                    }
+
+                    // Render matched string
+                    print_badge(&mut stdout, &age, Color::DarkGrey)?;
+                    print_badge(&mut stdout, &captures, Color::DarkCyan)?;
+                    print_badge(&mut stdout, &badge, Color::DarkGrey)?;
+                    print_highlighted(&mut stdout, matched_str, match_ranges, 0, matched_str.len(), &options)?;
+                    print_match_count(&mut stdout, match_ranges, options.show_match_counts)?;
+                    row += 1;
+                    continue;
+                }
+
+                // Wrap the matched string: the first chunk follows the path,
+                // the rest continue on indented rows below it
+                stdout.execute(Print(" ".repeat(gap)))?;
+                print_badge(&mut stdout, &age, Color::DarkGrey)?;
+                print_badge(&mut stdout, &captures, Color::DarkCyan)?;
+                print_badge(&mut stdout, &badge, Color::DarkGrey)?;
+                let mut chunk_width = text_width.saturating_sub(text_start + gap + badge_len).max(1);
+                let mut pos = 0;
+                while pos < matched_str.len() && row < max_rows {
+                    let mut end = matched_str.floor_char_boundary((pos + chunk_width).min(matched_str.len()));
+                    if end == pos {
+                        end = matched_str.ceil_char_boundary(pos + 1);
+                    }
+                    if pos > 0 {
                         stdout
-                            .execute(SetForegroundColor(Color::Cyan))?
-                            .execute(Print(&matched_str[last_pos..start]))?;
+                            .execute(MoveTo(0, results_start_row + row as u16))?
+                            .execute(Print(WRAP_INDENT))?;
                     }
-                    stdout
-                        .execute(SetForegroundColor(Color::Magenta))?
-                        .execute(Print(&matched_str[start..end]))?;
-                    last_pos = end;
+                    print_highlighted(&mut stdout, matched_str, match_ranges, pos, end, &options)?;
+                    pos = end;
+                    row += 1;
+                    chunk_width = text_width.saturating_sub(WRAP_INDENT.len()).max(1);
                 }
-                if last_pos < matched_str.len() {
-                    stdout
-                        .execute(SetForegroundColor(Color::Cyan))?
-                        .execute(Print(&matched_str[last_pos..]))?;
+                print_match_count(&mut stdout, match_ranges, options.show_match_counts)?;
+            }
+            rendered_rows = row;
+
+            // --heatmap: the blank row under the prompt shades each slice of
+            // the list by its matches, with the slices on screen underlaid
+            if options.heatmap && !results_only {
+                let shades = heatmap(&current_results, &files, results_width);
+                let len = current_results.len();
+                stdout.execute(MoveTo(0, prompt_row + 1))?;
+                for (column, shade) in shades.iter().enumerate() {
+                    let (start, end) = heat_slice(column, len, results_width);
+                    if start < offset + visible_results && end > offset {
+                        stdout.execute(SetBackgroundColor(Color::DarkGrey))?;
+                    }
+                    stdout.execute(SetForegroundColor(Color::Yellow))?.execute(Print(shade))?.execute(ResetColor)?;
                 }
+                stdout.execute(Print(" ".repeat(results_width.saturating_sub(shades.len()))))?;
+            }
+
+            // An empty list for a non-empty query would look like a hang,
+            // so say so (an empty query just lists files)
+            let searching = !query.is_empty() || !options.patterns.is_empty();
+            if current_results.is_empty() && searching && !regex_error && max_rows > 0 {
+                let column = results_width.saturating_sub(strings.no_matches.chars().count()) / 2;
+                stdout
+                    .execute(MoveTo(column as u16, results_start_row + (max_rows / 2) as u16))?
+                    .execute(SetForegroundColor(Color::DarkGrey))?
+                    .execute(Print(&strings.no_matches))?
+                    .execute(ResetColor)?;
+            }
+
+            if show_preview {
+                stdout.execute(SetForegroundColor(Color::DarkGrey))?;
+                let area = if side_preview {
+                    for i in 0..max_rows as u16 {
+                        stdout
+                            .execute(MoveTo(list_width as u16, results_start_row + i))?
+                            .execute(Print('│'))?;
+                    }
+                    preview::Area {
+                        x: list_width as u16 + 1,
+                        y: results_start_row,
+                        width: terminal_width.saturating_sub(list_width + 1),
+                        height: max_rows,
+                    }
+                } else {
+                    stdout
+                        .execute(MoveTo(0, results_start_row + max_rows as u16))?
+                        .execute(Print("─".repeat(terminal_width)))?;
+                    preview::Area {
+                        x: 0,
+                        y: results_start_row + max_rows as u16 + 1,
+                        width: terminal_width,
+                        height: region_rows.saturating_sub(max_rows + 1),
+                    }
+                };
                 stdout.execute(ResetColor)?;
+                preview_rows = area.height;
+                if let Some(result) = current_results.get(selected, &files).filter(|r| !r.path.is_empty()) {
+                    let binary = options.binary && !is_text_file(Path::new(&result.path), &extensions);
+                    if let Some(content) = load_content(&result.path, binary, &mut content_cache, &options) {
+                        preview::draw(&mut stdout, &area, &result.path, &content, result.file_line(), context, &options)?;
+                    }
+                }
             }
+
+            draw_scrollbar(
+                &mut stdout,
+                list_width as u16 - 1,
+                results_start_row,
+                max_rows,
+                offset,
+                visible_results,
+                current_results.len(),
+            )?;
         }
 
         stdout.flush()?;
 
         // Poll for keyboard events
-        if poll(Duration::from_millis(100))? {
-            if let Event::Key(KeyEvent { code, .. }) = read()? {
-                match code {
-                    KeyCode::Esc => break,
-                    KeyCode::Enter => {
-                        let last_results_len = current_results.len();
-                        query.clear();
-                        results_start_row = last_results_len as u16 + 3;
-                        for i in 0..(terminal_height - 3) {
+        // Keys pressed during the search come first
+        let event = match pending_events.pop_front() {
+            Some(event) => Some(event),
+            None if poll(Duration::from_millis(100))? => Some(read()?),
+            None => None,
+        };
+        if let Some(Event::Key(key)) = event {
+            status = None;
+            search_cancelled = false;
+            match key.code {
+                KeyCode::Esc => break,
+                _ if exec_key.matches(&key) => {
+                    // Act on the marked results if there are any, else the selected one
+                    let mut targets: Vec<usize> = if marked.is_empty() {
+                        vec![selected]
+                    } else {
+                        marked.iter().copied().collect()
+                    };
+                    targets.sort_unstable();
+                    let targets: Vec<SearchResult> = targets
+                        .into_iter()
+                        .filter_map(|i| current_results.get(i, &files).map(Cow::into_owned))
+                        .filter(|result| !result.path.is_empty())
+                        .collect();
+                    if let Some(template) = &options.exec
+                        && !targets.is_empty()
+                    {
+                        // Hand the terminal to the command, then rebuild the screen
+                        terminal::disable_raw_mode()?;
+                        stdout.execute(Clear(ClearType::All))?.execute(MoveTo(0, 0))?.execute(Show)?;
+                        let status = exec::run_all(template, &targets);
+                        terminal::enable_raw_mode()?;
+                        stdout.execute(Clear(ClearType::All))?;
+                        if let Err(e) = status {
+                            stdout
+                                .execute(MoveTo(0, terminal_height - 1))?
+                                .execute(SetForegroundColor(Color::Red))?
+                                .execute(Print(format!("exec failed: {}", e)))?
+                                .execute(ResetColor)?;
+                        }
+                        prompt_row = 0;
+                        if results_only {
+                            results_start_row = 0;
+                            stdout.execute(Hide)?;
+                        } else {
+                            results_start_row = 2;
+                            stdout.execute(MoveTo(0, 0))?.execute(Print(&strings.prompt))?;
+                        }
+                        needs_redraw = true;
+                    }
+                }
+                KeyCode::F(5) => {
+                    reload(&mut files, &mut content_cache, &mut status, &mut source_job, &extensions, &mut options);
+                    modified_times.clear();
+                    searched = None;
+                }
+                KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    reload(&mut files, &mut content_cache, &mut status, &mut source_job, &extensions, &mut options);
+                    modified_times.clear();
+                    searched = None;
+                }
+                // Hide the prompt and give the results the whole screen;
+                // the query stays as it is until `/` brings the prompt back
+                KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) && !results_only => {
+                    results_only = true;
+                    committed.clear();
+                    changes_view = changes::View::Off;
+                    prompt_row = 0;
+                    results_start_row = 0;
+                    stdout.execute(Clear(ClearType::All))?.execute(Hide)?;
+                    needs_redraw = true;
+                }
+                // `/` (as in less) or Ctrl-F again
+                KeyCode::Char('/' | 'f')
+                    if results_only && (key.code == KeyCode::Char('/') || key.modifiers.contains(KeyModifiers::CONTROL)) =>
+                {
+                    results_only = false;
+                    results_start_row = 2;
+                    stdout
+                        .execute(Clear(ClearType::All))?
+                        .execute(MoveTo(0, 0))?
+                        .execute(Print(&strings.prompt))?
+                        .execute(Show)?;
+                    needs_redraw = true;
+                }
+                // Switch between plain text and regex queries
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::ALT) => {
+                    options.literal = !options.literal;
+                    status = Some(if options.literal { "query: literal" } else { "query: regex" }.to_string());
+                    searched = None;
+                }
+                // Plain + and - are query text, so the context keys take Alt.
+                // Context past half the pane is the same as filling it.
+                KeyCode::Char('+' | '=' | '-') if key.modifiers.contains(KeyModifiers::ALT) => {
+                    let full = preview_rows / 2;
+                    let current = context.unwrap_or(full).min(full);
+                    context = match key.code {
+                        KeyCode::Char('-') => Some(current.saturating_sub(1)),
+                        _ if current + 1 >= full => None,
+                        _ => Some(current + 1),
+                    };
+                    status = Some(match context {
+                        Some(lines) => format!("context: {}", lines),
+                        None => "context: full".to_string(),
+                    });
+                    needs_redraw = true;
+                }
+                // Undo the last Enter: drop the new block and take up the
+                // previous query where it was. A block scrolled off the top
+                // comes back at the top row.
+                KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) && !results_only => {
+                    if let Some((row, previous, _)) = committed.pop() {
+                        if committed.is_empty() {
+                            changes_view = changes::View::Off;
+                        }
+                        for i in row..terminal_height {
                             stdout
-                                .execute(MoveTo(0, results_start_row + i as u16))?
+                                .execute(MoveTo(0, i))?
                                 .execute(Print(" ".repeat(terminal_width)))?;
                         }
+                        prompt_row = row;
+                        results_start_row = prompt_row + 2;
+                        query = previous;
                         stdout
-                            .execute(MoveTo(0, results_start_row - 1))?
-                            .execute(Print("Search: "))?;
-                        current_results.clear();
+                            .execute(MoveTo(0, prompt_row))?
+                            .execute(Print(&strings.prompt))?;
+                        current_results = Rows::Results(Vec::new());
+                        searched = None;
+                        rendered_rows = 0;
                     }
-                    KeyCode::Backspace => {
-                        query.pop();
+                }
+                // Mark the results added since the last Enter, then show only
+                // those, then go back to all of them
+                KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if committed.is_empty() {
+                        status = Some("no earlier Enter to compare with".to_string());
+                    } else {
+                        changes_view = changes_view.next();
+                        searched = None;
+                        needs_redraw = true;
                     }
-                    KeyCode::Char(c) => {
-                        query.push(c);
+                }
+                // Copy every result, not just the ones on screen, in full
+                KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    let results = full_results(&files, records.as_deref(), &mut content_cache, &query, &options);
+                    status = Some(copy_results(&mut stdout, &results, &options));
+                }
+                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    export_path = Some(String::new());
+                }
+                // Browse for a new root; the file list is rebuilt from there
+                KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if options.from_json || options.stdin {
+                        status = Some("stdin has no root to change".to_string());
+                    } else if options.exec_source.is_some() {
+                        status = Some("command output has no root to change".to_string());
+                    } else {
+                        let dir = std::env::current_dir().unwrap_or_else(|_| ".".into());
+                        picker = Some(picker::Picker::open(dir));
+                        needs_redraw = true;
+                    }
+                }
+                // The list takes the whole region while the preview is hidden
+                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    show_preview = !show_preview;
+                    needs_redraw = true;
+                }
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if options.search_roots.len() > 1 {
+                        show_root_labels = !show_root_labels;
+                        needs_redraw = true;
+                    } else {
+                        status = Some("root tags need several --path roots".to_string());
                     }
-                    _ => {}
                 }
+                KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    wrap = !wrap;
+                    needs_redraw = true;
+                }
+                KeyCode::Tab if selected < current_results.len() => {
+                    if !marked.remove(&selected) {
+                        marked.insert(selected);
+                    }
+                    needs_redraw = true;
+                }
+                KeyCode::Left | KeyCode::Right if let Rows::Tree(tree) = &mut current_results => {
+                    // Rows below a fold shift, so marks would land on others
+                    selected = tree.fold(selected, key.code == KeyCode::Left, &mut folded);
+                    marked.clear();
+                    needs_redraw = true;
+                }
+                KeyCode::Up if selected > 0 => {
+                    selected -= 1;
+                    needs_redraw = true;
+                }
+                KeyCode::Down if selected + 1 < current_results.len() => {
+                    selected += 1;
+                    needs_redraw = true;
+                }
+                KeyCode::PageUp => {
+                    selected = selected.saturating_sub(visible_results.max(1));
+                    needs_redraw = true;
+                }
+                KeyCode::PageDown if !current_results.is_empty() => {
+                    selected = (selected + visible_results.max(1)).min(current_results.len() - 1);
+                    needs_redraw = true;
+                }
+                KeyCode::Enter if !results_only => {
+                    // Start a new prompt one blank line below the rows just
+                    // shown. If that leaves less than half the screen for the
+                    // new results, scroll the terminal up to make room.
+                    let keys = current_keys.take().unwrap_or_else(|| changes::keys(&current_results, &files));
+                    committed.push((prompt_row, std::mem::take(&mut query), keys));
+                    prompt_row = results_start_row + rendered_rows as u16 + 1;
+                    let min_rows = terminal_height.saturating_sub(3) / 2;
+                    let overflow = (prompt_row + 2 + min_rows + 1).saturating_sub(terminal_height);
+                    if overflow > 0 {
+                        stdout.execute(ScrollUp(overflow))?;
+                        prompt_row -= overflow.min(prompt_row);
+                        for (row, _, _) in &mut committed {
+                            *row = row.saturating_sub(overflow);
+                        }
+                    }
+                    results_start_row = prompt_row + 2;
+                    for i in prompt_row..terminal_height {
+                        stdout
+                            .execute(MoveTo(0, i))?
+                            .execute(Print(" ".repeat(terminal_width)))?;
+                    }
+                    stdout
+                        .execute(MoveTo(0, prompt_row))?
+                        .execute(Print(&strings.prompt))?;
+                    current_results = Rows::Results(Vec::new());
+                    searched = None;
+                    rendered_rows = 0;
+                }
+                KeyCode::Backspace if !results_only => {
+                    query.pop();
+                }
+                KeyCode::Char(c) if !results_only => {
+                    query.push(c);
+                }
+                _ => {}
+            }
+        }
+
+        // --exec-source re-runs happen on a worker, since a streaming
+        // command runs for SOURCE_TIMEOUT each time; the UI takes the output
+        // once it is in. With --watch the next run starts SOURCE_INTERVAL
+        // after the last one ended.
+        if let Some(command) = &options.exec_source {
+            if let Some((job, announce)) = &source_job
+                && let Ok(output) = job.try_recv()
+            {
+                match take_source_output(command, output, &mut content_cache) {
+                    Ok((changed, note)) => {
+                        if changed {
+                            searched = None;
+                        }
+                        if *announce {
+                            status = Some(note);
+                        }
+                    }
+                    Err(e) => status = Some(e),
+                }
+                source_job = None;
+                source_run = Instant::now();
+            }
+            if options.watch && source_job.is_none() && source_run.elapsed() >= SOURCE_INTERVAL {
+                source_job = Some((exec::capture_in_background(command), false));
+            }
+        }
+
+        // --watch: forget the cached contents of changed files and search
+        // again; the redraw also refreshes the preview
+        if let Some(watcher) = &mut watcher {
+            let changed = watcher.poll(&files);
+            if !changed.is_empty() {
+                let stale: Vec<String> = content_cache
+                    .iter()
+                    .map(|(key, _)| key.clone())
+                    .filter(|key| changed.iter().any(|path| path == watch::on_disk(key)))
+                    .collect();
+                for key in stale {
+                    content_cache.pop(&key);
+                }
+                modified_times.retain(|key, _| !changed.iter().any(|path| path == watch::on_disk(key)));
+                searched = None;
             }
         }
     }
 
     // Cleanup: disable raw mode, position cursor dynamically, show cursor
     terminal::disable_raw_mode()?;
-    let exit_row = if current_results.len() >= (terminal_height - 3) as usize {
-        // Many results: use terminal_height - 2 (leaves one blank line)
-        terminal_height - 2
-    } else {
-        // Few results: use row after last result
-        results_start_row + current_results.len() as u16
-    };
+    // Row after the last rendered result, which stays on screen because the
+    // results area never reaches the final row
+    let exit_row = (results_start_row + rendered_rows as u16).min(terminal_height - 1);
     stdout
         .execute(MoveTo(0, exit_row))?
         .execute(Show)?;
     Ok(())
 }
 
+// Number of files whose contents are kept in memory between searches
+const CONTENT_CACHE_SIZE: usize = 100;
+
+// Name under which --stdin text is listed and cached
+const STDIN_PATH: &str = "<stdin>";
+// The same for --exec-source's command output
+const SOURCE_PATH: &str = "<cmd output>";
+// How often --watch runs --exec-source's command again, counted from the
+// end of the last run
+const SOURCE_INTERVAL: Duration = Duration::from_secs(2);
+
+// An --exec-source run on a worker, and whether to report it when it ends
+type SourceJob = (mpsc::Receiver<std::io::Result<exec::Output>>, bool);
+
+// With --stdin or --exec-source the text is put in the cache up front. It
+// is the only file then, so it is never evicted.
+fn new_content_cache(document: Option<(&str, String)>) -> LruCache<String, String> {
+    let mut cache = LruCache::new(NonZeroUsize::new(CONTENT_CACHE_SIZE).expect("Cache size must be non-zero"));
+    if let Some((path, text)) = document {
+        cache.put(path.to_string(), text);
+    }
+    cache
+}
+
+// Put a finished re-run's output in the cache in place of the last one.
+// Returns whether the text changed and the note for the status row; a
+// failed run keeps the old text.
+fn take_source_output(
+    command: &str,
+    output: std::io::Result<exec::Output>,
+    content_cache: &mut LruCache<String, String>,
+) -> Result<(bool, String), String> {
+    let output = output.map_err(|e| format!("cannot run {}: {}", command, e))?;
+    let lines = output.text.lines().count();
+    let changed = content_cache.peek(SOURCE_PATH) != Some(&output.text);
+    content_cache.put(SOURCE_PATH.to_string(), output.text);
+    let note = match output.cut {
+        Some(cut) => format!("re-ran ({} lines, cut: {})", lines, cut),
+        None => format!("re-ran ({} lines)", lines),
+    };
+    Ok((changed, note))
+}
+
+// Print the settings in effect after combining flags, the environment and
+// built-in defaults, one `key = value` per line
+fn show_config(options: &Options, extensions: &[String], severities: &[(String, severity::Severity)]) {
+    let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_string());
+    let color_name = |color: Color| format!("{:?}", color).to_lowercase();
+    // Config entries first, then the built-in colors they leave in effect
+    let path_colors = options
+        .ext_colors
+        .iter()
+        .cloned()
+        .chain(
+            EXTENSION_COLORS
+                .iter()
+                .filter(|(ext, _)| !options.ext_colors.iter().any(|(known, _)| known == ext))
+                .map(|&(ext, color)| (ext.to_string(), color)),
+        )
+        .map(|(ext, color)| format!("{}={}", ext, color_name(color)))
+        .collect::<Vec<_>>();
+    // The query's case follows --path-case when it matches paths
+    let matches_paths = options.paths || options.glob || options.fuzzy;
+    let sort = match options.sort {
+        SortOrder::Walk => "walk",
+        SortOrder::Recency => "recency",
+        SortOrder::Path if options.natural_sort => "path (natural)",
+        SortOrder::Path => "path",
+    };
+    let source = if options.from_json {
+        "json records on stdin"
+    } else if options.stdin {
+        "text on stdin"
+    } else if options.exec_source.is_some() {
+        "command output"
+    } else if options.rev.is_some() {
+        "git revision"
+    } else if options.tracked_only {
+        "git tracked files"
+    } else {
+        "directory walk"
+    };
+    let settings = [
+        ("extensions", extensions.join(",")),
+        ("ext_map", options.ext_map.iter().map(|(from, to)| format!("{}={}", from, to)).collect::<Vec<_>>().join(",")),
+        ("binary", options.binary.to_string()),
+        ("byte_offsets", options.byte_offsets.to_string()),
+        ("search_archives", options.search_archives.to_string()),
+        ("files", source.to_string()),
+        ("exec_source", or_none(&options.exec_source)),
+        ("follow_symlinks", options.follow_symlinks.to_string()),
+        ("watch", options.watch.to_string()),
+        ("dot_prefix", options.dot_prefix.to_string()),
+        ("elide_middle", options.elide_middle.to_string()),
+        ("git_diff", options.git_diff.to_string()),
+        ("rev", or_none(&options.rev)),
+        ("root", or_none(&options.root)),
+        ("query_fifo", or_none(&options.query_fifo)),
+        ("gitignore", options.gitignore.to_string()),
+        ("shebang", or_none(&options.shebang)),
+        ("paths_searched", if options.search_roots.is_empty() { ".".to_string() } else { options.search_roots.join(", ") }),
+        ("config", config::path().map_or("none".to_string(), |path| path.display().to_string())),
+        ("case", format!("{:?}", if matches_paths { options.path_case } else { search::CONTENT_CASE }).to_lowercase()),
+        ("path_case", format!("{:?}", options.path_case).to_lowercase()),
+        ("match", if options.fuzzy { "path fuzzy" } else if options.glob { "path glob" } else if options.paths { "path regex" } else { "contents" }.to_string()),
+        ("patterns", options.patterns.join(", ")),
+        ("not", options.excludes.join(", ")),
+        ("prefilter", or_none(&options.prefilter)),
+        ("loose", options.loose.to_string()),
+        ("normalize", options.normalize.to_string()),
+        ("line_regexp", options.line_regexp.to_string()),
+        ("only_matching", options.only_matching.to_string()),
+        ("in_comments", options.in_comments.to_string()),
+        ("in_strings", options.in_strings.to_string()),
+        ("keep_cr", options.keep_cr.to_string()),
+        ("unique", options.unique.to_string()),
+        ("sort", sort.to_string()),
+        ("recency_weight", options.recency_weight.unwrap_or(rank::DEFAULT_RECENCY_WEIGHT).to_string()),
+        ("wrap", options.wrap.to_string()),
+        ("show_match_counts", options.show_match_counts.to_string()),
+        ("heatmap", options.heatmap.to_string()),
+        ("results_only", options.results_only.to_string()),
+        ("show_captures", options.show_captures.to_string()),
+        ("dim_context", options.dim_context.to_string()),
+        ("show_whitespace", options.show_whitespace.to_string()),
+        ("trim", options.trim.to_string()),
+        ("collapse_whitespace", options.collapse_whitespace.to_string()),
+        ("field", options.field.map_or("none".to_string(), |n| format!("{} (delimiter {:?})", n, options.delimiter.unwrap_or(',')))),
+        ("show_age", options.show_age.to_string()),
+        ("preview", options.preview.to_string()),
+        ("syntax_highlight", options.syntax_highlight.to_string()),
+        ("layout", if options.layout == Layout::Vertical { "vertical" } else { "horizontal" }.to_string()),
+        ("highlight_style", format!("{:?}", options.highlight_style).to_lowercase()),
+        ("preview_focus", options.preview_focus.map_or("none".to_string(), color_name)),
+        ("severity", severities.iter().map(|(word, severity)| format!("{}={} {}", word, color_name(severity.color), severity.weight)).collect::<Vec<_>>().join(", ")),
+        ("path_colors", path_colors.join(",")),
+        ("format", or_none(&options.format)),
+        ("pager", options.pager.to_string()),
+        ("exec", or_none(&options.exec)),
+        ("exec_key", options.exec_key.unwrap_or(DEFAULT_EXEC_KEY).to_string()),
+        ("timeout_ms", options.timeout_ms.map_or("none".to_string(), |ms| ms.to_string())),
+        ("file_timeout_ms", options.file_timeout_ms.map_or("none".to_string(), |ms| ms.to_string())),
+        ("initial_query", or_none(&options.initial_query)),
+        ("max_columns", options.max_columns.map_or("none".to_string(), |n| n.to_string())),
+        ("range", options.range.map_or("all".to_string(), |(start, end)| match end {
+            usize::MAX => format!("{}:", start),
+            end => format!("{}:{}", start, end),
+        })),
+        ("window", options.window.map_or("1".to_string(), |n| n.to_string())),
+        ("record_separator", options.record_separator.as_ref().map_or("newline".to_string(), |sep| format!("{:?}", sep))),
+        ("stats", options.stats.to_string()),
+        ("always_redraw", options.always_redraw.to_string()),
+        ("tree", options.tree.to_string()),
+        ("query_syntax", if options.literal { "literal" } else { "regex" }.to_string()),
+        ("context", options.context.map_or("full".to_string(), |n| n.to_string())),
+        ("min_size", options.min_size.map_or("none".to_string(), |n| n.to_string())),
+        ("max_size", options.max_size.map_or("none".to_string(), |n| n.to_string())),
+        ("threads", options.threads.filter(|&n| n > 0).map_or("auto".to_string(), |n| n.to_string())),
+        ("cache_size", CONTENT_CACHE_SIZE.to_string()),
+    ];
+    for (key, value) in settings {
+        println!("{} = {}", key, value);
+    }
+}
+
+// Exit codes for non-interactive runs, following grep: 0 if anything
+// matched, 1 if nothing did, 2 on error (e.g. an invalid pattern)
+const EXIT_MATCH: i32 = 0;
+const EXIT_NO_MATCH: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+fn run_batch(
+    files: &[String],
+    records: Option<&[SearchResult]>,
+    content_cache: &mut LruCache<String, String>,
+    query: &str,
+    options: &Options,
+) -> i32 {
+    let mut stats = SearchStats::default();
+    let started = Instant::now();
+    let results = match records {
+        Some(records) => search_records(records, query, usize::MAX, options),
+        None => search_file_contents(files, query, content_cache, usize::MAX, options, &mut stats, &|| false),
+    };
+    if options.stats {
+        eprintln!(
+            "termiscope: scanned {} files, {} lines, {} bytes in {} ms",
+            stats.files,
+            stats.lines,
+            stats.bytes,
+            started.elapsed().as_millis()
+        );
+    }
+    if results.first().is_some_and(SearchResult::is_invalid_regex) {
+        eprintln!("termiscope: invalid {} pattern: {}", if options.glob { "glob" } else { "regex" }, query);
+        return EXIT_ERROR;
+    }
+    if stats.partial {
+        eprintln!("termiscope: search timed out, results are partial");
+    }
+    if stats.slow_files > 0 {
+        eprintln!("termiscope: skipped {} files that took longer than --file-timeout-ms", stats.slow_files);
+    }
+
+    // --pager only applies when a person is reading, like git's pager
+    let mut pager = None;
+    if options.pager && stdout().is_terminal() {
+        match spawn_pager() {
+            Ok(child) => pager = Some(child),
+            Err(e) => eprintln!("termiscope: cannot start pager: {}", e),
+        }
+    }
+    let mut out: Box<dyn Write> = match pager.as_mut().and_then(|child| child.stdin.take()) {
+        Some(input) => Box::new(input),
+        None => Box::new(stdout().lock()),
+    };
+    let written = if options.json {
+        output::write_json(&mut out, &results)
+    } else if options.markdown {
+        output::write_markdown(&mut out, &results)
+    } else if options.tree {
+        output::write_tree(&mut out, &Tree::new(results.clone(), &HashSet::new()))
+    } else if let Some(template) = &options.format {
+        output::write_formatted(&mut out, &results, &format::parse(template))
+    } else {
+        output::write_plain(&mut out, &results)
+    };
+    let written = written.and_then(|_| out.flush());
+    drop(out);
+    match written {
+        // Quitting the pager before the end closes the pipe, which is fine
+        Err(e) if pager.is_some() && e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("termiscope: {}", e);
+            return EXIT_ERROR;
+        }
+        Ok(()) => {}
+    }
+    if let Some(mut child) = pager {
+        let _ = child.wait();
+    }
+
+    if results.is_empty() {
+        EXIT_NO_MATCH
+    } else {
+        EXIT_MATCH
+    }
+}
+
+// The results of `query` in full, as --query would print them rather than
+// cut to the screen, for exporting
+fn full_results(
+    files: &[String],
+    records: Option<&[SearchResult]>,
+    content_cache: &mut LruCache<String, String>,
+    query: &str,
+    options: &Options,
+) -> Vec<SearchResult> {
+    let results = match records {
+        Some(records) => search_records(records, query, usize::MAX, options),
+        None if lists_all_files(query, options) => files.iter().map(|file| SearchResult::file(file)).collect(),
+        None => search_file_contents(files, query, content_cache, usize::MAX, options, &mut SearchStats::default(), &|| false),
+    };
+    results.into_iter().filter(|result| !result.is_invalid_regex()).collect()
+}
+
+// Write `results` to `path` in the format its extension names, with the
+// batch output writers
+fn export(path: &str, results: &[SearchResult], options: &Options) -> std::io::Result<()> {
+    let mut out = std::io::BufWriter::new(fs::File::create(path)?);
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json" | "jsonl") => output::write_json(&mut out, results)?,
+        Some("csv") => output::write_csv(&mut out, results)?,
+        Some("md") => output::write_markdown(&mut out, results)?,
+        _ => write_shown(&mut out, results, options)?,
+    }
+    out.flush()
+}
+
+// Results as --query prints them: `path:line: text` lines, or the
+// --format template
+fn write_shown<W: Write>(out: &mut W, results: &[SearchResult], options: &Options) -> std::io::Result<()> {
+    match &options.format {
+        Some(template) => output::write_formatted(out, results, &format::parse(template)),
+        None => output::write_plain(out, results),
+    }
+}
+
+// Terminals cap what OSC 52 may carry, and a paste this big is better
+// exported to a file
+const COPY_LIMIT: usize = 1024 * 1024;
+
+// Copy the results to the clipboard as --query prints them, returning the
+// status message
+fn copy_results(stdout: &mut Stdout, results: &[SearchResult], options: &Options) -> String {
+    let mut text = Vec::new();
+    if let Err(e) = write_shown(&mut text, results, options) {
+        return format!("cannot copy: {}", e);
+    }
+    if text.len() > COPY_LIMIT {
+        return format!("{} rows is {} KiB, over the copy limit; Ctrl-S exports them", results.len(), text.len() / 1024);
+    }
+    match clipboard::copy(stdout, &String::from_utf8_lossy(&text)) {
+        Ok(()) => format!("copied {} row{}", results.len(), if results.len() == 1 { "" } else { "s" }),
+        Err(e) => format!("cannot copy: {}", e),
+    }
+}
+
+// $PAGER, else `less -R`, reading the results on its stdin
+fn spawn_pager() -> std::io::Result<Child> {
+    let pager = std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty());
+    Command::new("sh")
+        .arg("-c")
+        .arg(pager.as_deref().unwrap_or("less -R"))
+        .stdin(Stdio::piped())
+        .spawn()
+}
+
+// Draw a scrollbar in column `x` spanning `rows` rows from `top`, with a
+// thumb sized and placed by the visible window within the whole list
+fn draw_scrollbar(
+    stdout: &mut Stdout,
+    x: u16,
+    top: u16,
+    rows: usize,
+    offset: usize,
+    window: usize,
+    total: usize,
+) -> std::io::Result<()> {
+    if total <= window || rows == 0 {
+        return Ok(());
+    }
+    let thumb_size = (window * rows / total).clamp(1, rows);
+    let thumb_start = (offset * rows / total).min(rows - thumb_size);
+    stdout.execute(SetForegroundColor(Color::DarkGrey))?;
+    for row in 0..rows {
+        let glyph = if (thumb_start..thumb_start + thumb_size).contains(&row) {
+            '█'
+        } else {
+            '│'
+        };
+        stdout
+            .execute(MoveTo(x, top + row as u16))?
+            .execute(Print(glyph))?;
+    }
+    stdout.execute(ResetColor)?;
+    Ok(())
+}
+
+// Columns reserved at the end of each row for the " ×N" match count badge
+const MATCH_COUNT_WIDTH: usize = 5;
+
+// Dimmed " ×N" after a row whose line matched more than once
+fn print_match_count(stdout: &mut Stdout, ranges: &[MatchRange], enabled: bool) -> std::io::Result<()> {
+    if enabled && ranges.len() > 1 {
+        stdout
+            .execute(SetForegroundColor(Color::DarkGrey))?
+            .execute(Print(format!(" ×{}", ranges.len())))?
+            .execute(ResetColor)?;
+    }
+    Ok(())
+}
+
+// --heatmap shades, from no matches to the busiest slice of the list
+const HEAT: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+// Match density down the result list, one shade per column. Each column
+// stands for an equal slice of the list (a short list repeats rows across
+// columns) and is shaded by its matches against the busiest slice's. Rows
+// that only name a file hold no matches, so a file listing shows nothing.
+fn heatmap(rows: &Rows, files: &[String], columns: usize) -> Vec<char> {
+    let len = rows.len();
+    if len == 0 || matches!(rows, Rows::Listing(_)) {
+        return Vec::new();
+    }
+    let counts: Vec<usize> = (0..len)
+        .map(|index| rows.get(index, files).map_or(0, |row| if row.line == 0 { 0 } else { row.ranges.len().max(1) }))
+        .collect();
+    let slices: Vec<usize> = (0..columns)
+        .map(|column| {
+            let (start, end) = heat_slice(column, len, columns);
+            counts[start..end].iter().sum()
+        })
+        .collect();
+    let busiest = slices.iter().copied().max().unwrap_or(0);
+    slices
+        .into_iter()
+        .map(|matches| match matches {
+            0 => HEAT[0],
+            matches => HEAT[(matches * 4).div_ceil(busiest).clamp(1, 4)],
+        })
+        .collect()
+}
+
+// The rows of a `len`-row list that heatmap column `column` of `columns`
+// stands for, at least one
+fn heat_slice(column: usize, len: usize, columns: usize) -> (usize, usize) {
+    let start = (column * len / columns).min(len.saturating_sub(1));
+    let end = ((column + 1) * len / columns).clamp(start + 1, len.max(1));
+    (start, end)
+}
+
+// "412 files 18.3k ln 2.1M", short enough for the status field
+fn stats_readout(stats: &SearchStats) -> String {
+    let short = |n: usize, units: [&str; 3]| match n {
+        0..1_000 => format!("{}{}", n, units[0]),
+        1_000..1_000_000 => format!("{:.1}{}", n as f64 / 1e3, units[1]),
+        _ => format!("{:.1}{}", n as f64 / 1e6, units[2]),
+    };
+    let readout = format!(
+        "{} files {} ln {}",
+        stats.files,
+        short(stats.lines, ["", "k", "M"]),
+        short(stats.bytes, ["B", "K", "M"])
+    );
+    match stats.slow_files {
+        0 => readout,
+        slow => format!("{} {} slow", readout, slow),
+    }
+}
+
+// How often a running search looks for key presses
+const KEY_CHECK_INTERVAL: Duration = Duration::from_millis(30);
+
+// Watches the keyboard while a search runs: Esc cancels the search, other
+// events are kept in `pending` for the main loop. Search workers call
+// `check` between files, so it is rate-limited and safe to share.
+struct KeyWatch {
+    cancelled: AtomicBool,
+    pending: Mutex<Vec<Event>>,
+    last_check: Mutex<Instant>,
+}
+
+impl KeyWatch {
+    fn new() -> KeyWatch {
+        KeyWatch {
+            cancelled: AtomicBool::new(false),
+            pending: Mutex::new(Vec::new()),
+            last_check: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn check(&self) -> bool {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+        {
+            let mut last_check = self.last_check.lock().unwrap();
+            if last_check.elapsed() < KEY_CHECK_INTERVAL {
+                return false;
+            }
+            *last_check = Instant::now();
+        }
+        while poll(Duration::ZERO).unwrap_or(false) {
+            let Ok(event) = read() else {
+                break;
+            };
+            if let Event::Key(key) = &event
+                && key.code == KeyCode::Esc
+            {
+                self.cancelled.store(true, Ordering::Relaxed);
+                return true;
+            }
+            self.pending.lock().unwrap().push(event);
+        }
+        false
+    }
+}
+
+// Room kept at the end of the prompt row for the partial flag and status messages
+const STATUS_WIDTH: usize = 24;
+
+// "⇥8 " for a line that had 8 bytes of indentation trimmed, else nothing
+fn indent_badge(indent: usize) -> String {
+    if indent == 0 {
+        String::new()
+    } else {
+        format!("⇥{} ", indent)
+    }
+}
+
+// "(2h ago) " for --show-age, nothing for a file without a modification
+// time (stdin, a file since deleted)
+fn age_badge(path: &str, modified_times: &mut HashMap<String, Option<SystemTime>>) -> String {
+    let modified = *modified_times
+        .entry(path.to_string())
+        .or_insert_with(|| fs::metadata(watch::on_disk(path)).and_then(|meta| meta.modified()).ok());
+    match modified {
+        // A time in the future (clock skew) counts as now
+        Some(modified) => format!("({} ago) ", dates::compact(SystemTime::now().duration_since(modified).unwrap_or_default())),
+        None => String::new(),
+    }
+}
+
+// "ver=1.2 " for each named group captured with --show-captures
+fn captures_badge(captures: &[(String, String)]) -> String {
+    captures.iter().map(|(name, value)| format!("{}={} ", name, value)).collect()
+}
+
+fn print_badge(stdout: &mut Stdout, badge: &str, color: Color) -> std::io::Result<()> {
+    if !badge.is_empty() {
+        stdout
+            .execute(SetForegroundColor(color))?
+            .execute(Print(badge))?
+            .execute(ResetColor)?;
+    }
+    Ok(())
+}
+
+// Tag colors of the --path roots, in order
+const ROOT_COLORS: &[Color] = &[Color::Cyan, Color::Green, Color::Yellow, Color::Magenta, Color::Blue, Color::Red];
+
+// Each --path root's directory name, or the root as given where two
+// names would be the same
+fn root_labels(roots: &[String]) -> Vec<String> {
+    let name = |root: &str| Path::new(root).file_name().map_or(root.to_string(), |name| name.to_string_lossy().into_owned());
+    roots
+        .iter()
+        .map(|root| match name(root) {
+            label if roots.iter().filter(|other| name(other) == label).count() > 1 => root.clone(),
+            label => label,
+        })
+        .collect()
+}
+
+// The --path root `path` was walked from, by index, and the path below it.
+// Where roots overlap the longest wins. The walk's "./" may be dropped
+// from paths, so it is ignored on both.
+fn split_root<'a>(path: &'a str, roots: &[String]) -> Option<(usize, &'a str)> {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    roots
+        .iter()
+        .enumerate()
+        .filter_map(|(index, root)| {
+            let rest = match root.strip_prefix("./").unwrap_or(root) {
+                "." => path,
+                root if root.ends_with('/') => path.strip_prefix(root)?,
+                root => path.strip_prefix(root)?.strip_prefix('/')?,
+            };
+            Some((index, rest))
+        })
+        .max_by_key(|&(index, _)| roots[index].len())
+}
+
+// Prefix drawn before the path of a marked result
+const MARK_GLYPH: &str = "* ";
+
+// Continuation rows of a wrapped result are indented by this much
+const WRAP_INDENT: &str = "    ";
+
+// Result lists narrower than this stack each result's path above its text
+const NARROW_WIDTH: usize = 60;
+// Indent of the text row of a stacked result
+const STACK_INDENT: &str = "  ";
+
+// Print a result row laid out by a --format template, clipped to `width`
+// columns. The path keeps its file type color and selection highlight and
+// {text} keeps its match highlighting.
+fn print_formatted(
+    stdout: &mut Stdout,
+    parts: &[format::Part],
+    result: &SearchResult,
+    display_file: &str,
+    selected: bool,
+    width: usize,
+    options: &Options,
+) -> std::io::Result<()> {
+    let mut used = 0;
+    for part in parts {
+        if used >= width {
+            break;
+        }
+        let room = width - used;
+        match part {
+            format::Part::Path => {
+                let shown = clip(display_file, room);
+                if selected {
+                    stdout.execute(SetAttribute(Attribute::Reverse))?;
+                }
+                stdout
+                    .execute(SetForegroundColor(path_color(&result.path, options)))?
+                    .execute(Print(shown))?
+                    .execute(SetAttribute(Attribute::Reset))?
+                    .execute(ResetColor)?;
+                used += shown.chars().count();
+            }
+            format::Part::Line | format::Part::Column => {
+                let field = if *part == format::Part::Line { format::line_field(result) } else { format::column_field(result) };
+                let shown = clip(&field, room);
+                stdout
+                    .execute(SetForegroundColor(Color::DarkGrey))?
+                    .execute(Print(shown))?
+                    .execute(ResetColor)?;
+                used += shown.chars().count();
+            }
+            format::Part::Text => {
+                let shown = clip(&result.text, room);
+                print_highlighted(stdout, &result.text, &result.ranges, 0, shown.len(), options)?;
+                used += shown.chars().count();
+            }
+            format::Part::Literal(literal) => {
+                let shown = clip(literal, room);
+                stdout.execute(Print(shown))?;
+                used += shown.chars().count();
+            }
+        }
+    }
+    Ok(())
+}
+
+// Highlight colors by pattern index; the typed query keeps magenta
+const PATTERN_COLORS: &[Color] = &[
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+    Color::DarkYellow,
+];
+
+// A --pattern that is an annotation word like FIXME has its severity's
+// color; other patterns take turns through PATTERN_COLORS
+fn pattern_color(pattern: usize, options: &Options) -> Color {
+    match pattern.checked_sub(1).and_then(|index| options.severities.get(index)) {
+        Some(Some(severity)) => severity.color,
+        _ => PATTERN_COLORS[pattern % PATTERN_COLORS.len()],
+    }
+}
+
+// A --tree row: its connectors, then a directory or file name and match
+// count (with a + once folded), or a match's line number and text clipped
+// to `width`
+fn print_tree_row(
+    stdout: &mut Stdout,
+    tree: &Tree,
+    row: &tree::Row,
+    selected: bool,
+    width: usize,
+    options: &Options,
+) -> std::io::Result<()> {
+    stdout
+        .execute(SetForegroundColor(Color::DarkGrey))?
+        .execute(Print(clip(&row.prefix, width)))?
+        .execute(ResetColor)?;
+    let mut room = width.saturating_sub(row.prefix.chars().count());
+    if selected {
+        stdout.execute(SetAttribute(Attribute::Reverse))?;
+    }
+    match &row.kind {
+        tree::Kind::Node { name, count, folded, header, .. } => {
+            let color = if header.path.is_empty() { Color::Blue } else { path_color(&header.path, options) };
+            let name = clip(name, room);
+            room = room.saturating_sub(name.chars().count());
+            let count = format!(" ({}){}", count, if *folded { " +" } else { "" });
+            stdout
+                .execute(SetForegroundColor(color))?
+                .execute(Print(name))?
+                .execute(SetForegroundColor(Color::DarkGrey))?
+                .execute(Print(clip(&count, room)))?;
+        }
+        tree::Kind::Match(index) => {
+            let result = &tree.results[*index];
+            let number = format!("{}: ", result.line);
+            let number = clip(&number, room);
+            room = room.saturating_sub(number.chars().count());
+            stdout
+                .execute(SetForegroundColor(Color::DarkGrey))?
+                .execute(Print(number))?
+                .execute(ResetColor)?;
+            let end = result.text.floor_char_boundary(room.min(result.text.len()));
+            print_highlighted(stdout, &result.text, &result.ranges, 0, end, options)?;
+        }
+    }
+    stdout.execute(SetAttribute(Attribute::Reset))?.execute(ResetColor)?;
+    Ok(())
+}
+
+// Print the elided path (`shown`, the end of the result's path, fitted to
+// its column) in `color`, with the --fuzzy components it matched
+// highlighted where they were kept
+fn print_path(
+    stdout: &mut Stdout,
+    elided: &elide::Elided,
+    shown: &str,
+    result: &SearchResult,
+    color: Color,
+    options: &Options,
+) -> std::io::Result<()> {
+    let text = &elided.text;
+    let shift = result.path.len() - shown.len();
+    let mut last = 0;
+    stdout.execute(SetForegroundColor(color))?;
+    for &(at, kept_start, kept_end) in &elided.kept {
+        for &(start, end) in &result.path_ranges {
+            let start = start.saturating_sub(shift).max(kept_start);
+            let end = end.saturating_sub(shift).min(kept_end);
+            if start >= end {
+                continue;
+            }
+            let (start, end) = (at + start - kept_start, at + end - kept_start);
+            stdout
+                .execute(Print(&text[last..start]))?
+                .execute(SetForegroundColor(pattern_color(0, options)))?
+                .execute(Print(&text[start..end]))?
+                .execute(SetForegroundColor(color))?;
+            last = end;
+        }
+    }
+    stdout.execute(Print(&text[last..]))?;
+    Ok(())
+}
+
+fn print_stale_row(stdout: &mut Stdout, text: &str, selected: bool, width: usize) -> std::io::Result<()> {
+    if selected {
+        stdout.execute(SetAttribute(Attribute::Reverse))?;
+    }
+    stdout
+        .execute(SetForegroundColor(Color::DarkGrey))?
+        .execute(Print(clip(text, width)))?
+        .execute(SetAttribute(Attribute::Reset))?
+        .execute(ResetColor)?;
+    Ok(())
+}
+
+// Print `text[from..to]`, highlighting the parts covered by `ranges` in
+// their pattern's color
+fn print_highlighted(
+    stdout: &mut Stdout,
+    text: &str,
+    ranges: &[MatchRange],
+    from: usize,
+    to: usize,
+    options: &Options,
+) -> std::io::Result<()> {
+    let mut last_pos = from;
+    for &(start, end, pattern) in ranges {
+        let (start, end) = (start.max(last_pos), end.min(to));
+        if start >= end {
+            continue;
+        }
+        if start > last_pos {
+            print_context(stdout, text, ranges, last_pos, start, options)?;
+        }
+        stdout.execute(SetForegroundColor(pattern_color(pattern, options)))?;
+        match highlight_attributes(options.highlight_style) {
+            Some((on, off)) => {
+                stdout.execute(SetAttribute(on))?;
+                print_text(stdout, text, start, end, options)?;
+                // Only this attribute is undone, so nothing bleeds into the context
+                stdout.execute(SetAttribute(off))?;
+            }
+            None => print_text(stdout, text, start, end, options)?,
+        }
+        last_pos = end;
+    }
+    if last_pos < to {
+        print_context(stdout, text, ranges, last_pos, to, options)?;
+    }
+    stdout.execute(ResetColor)?;
+    Ok(())
+}
+
+// The attribute that --highlight-style adds to matches and the one that
+// turns it off again
+fn highlight_attributes(style: HighlightStyle) -> Option<(Attribute, Attribute)> {
+    match style {
+        HighlightStyle::Color => None,
+        HighlightStyle::Bold => Some((Attribute::Bold, Attribute::NormalIntensity)),
+        HighlightStyle::Underline => Some((Attribute::Underlined, Attribute::NoUnderline)),
+        HighlightStyle::Reverse => Some((Attribute::Reverse, Attribute::NoReverse)),
+    }
+}
+
+// With --dim-context, only this many bytes either side of a match stay bright
+const DIM_WINDOW: usize = 12;
+
+// Print the non-matching text between `from` and `to`. With --dim-context,
+// the parts further than DIM_WINDOW from any match are drawn in grey.
+fn print_context(
+    stdout: &mut Stdout,
+    text: &str,
+    ranges: &[MatchRange],
+    from: usize,
+    to: usize,
+    options: &Options,
+) -> std::io::Result<()> {
+    if !options.dim_context {
+        stdout.execute(SetForegroundColor(Color::Cyan))?;
+        return print_text(stdout, text, from, to, options);
+    }
+    // The nearest matches may sit outside from..to when wrapping splits a line
+    let previous_end = ranges.iter().map(|&(_, end, _)| end).filter(|&end| end <= from).max();
+    let next_start = ranges.iter().map(|&(start, _, _)| start).filter(|&start| start >= to).min();
+    let lead_end = previous_end.map_or(from, |end| text.ceil_char_boundary((end + DIM_WINDOW).clamp(from, to)));
+    let tail_start = next_start.map_or(to, |start| {
+        text.floor_char_boundary(start.saturating_sub(DIM_WINDOW).clamp(lead_end, to))
+    });
+    for (start, end, color) in [
+        (from, lead_end, Color::Cyan),
+        (lead_end, tail_start, Color::DarkGrey),
+        (tail_start, to, Color::Cyan),
+    ] {
+        if start < end {
+            stdout.execute(SetForegroundColor(color))?;
+            print_text(stdout, text, start, end, options)?;
+        }
+    }
+    Ok(())
+}
+
+// Print `text[from..to]` in the current color. With --show-whitespace, the
+// whitespace ending `text` gets a background and invisible characters are
+// drawn as symbols.
+fn print_text(stdout: &mut Stdout, text: &str, from: usize, to: usize, options: &Options) -> std::io::Result<()> {
+    if !options.show_whitespace {
+        stdout.execute(Print(&text[from..to]))?;
+        return Ok(());
+    }
+    let trailing = text.trim_end().len().clamp(from, to);
+    whitespace::print(stdout, &text[from..trailing], false)?;
+    whitespace::print(stdout, &text[trailing..to], true)
+}
+
 fn is_not_hidden(entry: &DirEntry) -> bool {
     if entry.file_type().is_dir() {
         true
@@ -173,126 +1927,313 @@ fn is_not_hidden(entry: &DirEntry) -> bool {
     }
 }
 
-fn collect_text_files() -> Vec<String> {
-    let mut files = Vec::new();
-    for entry in WalkDir::new(".")
-        .into_iter()
-        .filter_entry(|e| is_not_hidden(e))
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file())
-    {
-        let path = entry.path();
-        if is_text_file(path) {
-            if let Some(path_str) = path.to_str() {
-                files.push(path_str.to_string());
-            }
+// Make a bookmarked directory (or, failing that, a directory path) the
+// current one, so the walk, git listings and result paths start there
+fn enter_root(root: &str) -> Result<(), Error> {
+    let config = config::load().map_err(Error::Config)?;
+    let dir = match config.bookmark(root) {
+        Some(dir) => config::expand_home(dir),
+        None if Path::new(root).is_dir() => Path::new(root).to_path_buf(),
+        None => return Err(Error::Input(format!("unknown root: {} is neither a bookmark nor a directory", root))),
+    };
+    std::env::set_current_dir(&dir).map_err(|e| Error::Input(format!("cannot enter {}: {}", dir.display(), e)))
+}
+
+// Why there is nothing to search: where the files were looked for and the
+// settings that narrowed them down
+fn no_files_note(extensions: &[String], options: &Options) -> String {
+    let place = match &options.rev {
+        Some(rev) => format!("at {}", rev),
+        None if options.search_roots.is_empty() => "here".to_string(),
+        None => format!("under {}", options.search_roots.join(", ")),
+    };
+    let mut settings = Vec::new();
+    if options.extensions.is_some() || !options.add_extensions.is_empty() {
+        settings.push(format!("extensions {}", extensions.join(",")));
+    }
+    for (set, name) in [
+        (options.shebang.is_some(), "--shebang"),
+        (options.tracked_only, "--tracked-only"),
+        (options.gitignore, "--gitignore"),
+        (options.changed_lines.is_some(), "--git-diff"),
+        (options.modified_since.is_some(), "--modified-since/within"),
+        (options.min_size.is_some() || options.max_size.is_some(), "size limits"),
+    ] {
+        if set {
+            settings.push(name.to_string());
         }
     }
-    files
+    if settings.is_empty() {
+        format!("no files to search {}", place)
+    } else {
+        format!("no files to search {} with {}", place, settings.join(", "))
+    }
 }
 
-const TEXT_EXTENSIONS: &[&str] = &[
-    "txt", "md", "rs", "py", "js", "ts", "html", "css", "json", "yaml", "yml", "toml", "ini", "sh",
-    "bash", "cpp", "c", "h", "java", "go", "rb", "php", "sql",
-];
+// The files to search: a git revision's, the tracked ones, or a walk of the tree
+fn collect_files(extensions: &[String], options: &Options) -> Result<Vec<String>, String> {
+    if let Some(root) = options.search_roots.iter().find(|root| !Path::new(root).is_dir()) {
+        return Err(format!("cannot search {}: not a directory", root));
+    }
+    let shebang = match &options.shebang {
+        Some(pattern) => Some(Regex::new(pattern).map_err(|e| format!("invalid --shebang pattern: {}", e))?),
+        None => None,
+    };
+    let files = match &options.rev {
+        Some(rev) => collect_revision_files(rev, extensions).map_err(|e| format!("cannot list files at {}: {}", rev, e))?,
+        None if options.tracked_only => collect_tracked_files(extensions, options, shebang.as_ref()),
+        None => collect_text_files(extensions, options, shebang.as_ref()),
+    };
+    // --git-diff leaves out files with nothing changed
+    let files = match &options.changed_lines {
+        Some(changed) => files
+            .into_iter()
+            .filter(|path| changed.contains_key(path.strip_prefix("./").unwrap_or(path)))
+            .collect(),
+        None => files,
+    };
+    // --gitignore: tracked files and git revisions hold nothing ignored
+    let files = if options.gitignore && options.rev.is_none() && !options.tracked_only {
+        drop_ignored(files, options)
+    } else {
+        files
+    };
+    // The walk's "./" only takes room in the path column and in piped
+    // output, so it is dropped unless --dot-prefix asks for it
+    if options.dot_prefix {
+        return Ok(files);
+    }
+    Ok(files
+        .into_iter()
+        .map(|path| match path.strip_prefix("./") {
+            Some(relative) => relative.to_string(),
+            None => path,
+        })
+        .collect())
+}
 
-fn is_text_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
-        .unwrap_or(false)
+// Walked files less those git ignores under each root. Paths are checked
+// with each of their directories, since git names an ignored directory
+// rather than its files. A root outside a repository drops nothing.
+fn drop_ignored(files: Vec<String>, options: &Options) -> Vec<String> {
+    let roots = if options.search_roots.is_empty() { vec![".".to_string()] } else { options.search_roots.clone() };
+    let ignored: Vec<(&String, HashSet<String>)> = roots
+        .iter()
+        .filter_map(|root| Some((root, git::ignored_paths(root).ok()?.into_iter().collect())))
+        .collect();
+    files
+        .into_iter()
+        .filter(|file| {
+            // An archive's entries go with the archive
+            let file = archive::split(file).map_or(file.as_str(), |(archive, _)| archive);
+            !ignored.iter().any(|(root, paths)| {
+                let Some(relative) = file.strip_prefix(root.as_str()).and_then(|rest| rest.strip_prefix('/')) else {
+                    return false;
+                };
+                paths.contains(relative)
+                    || relative.match_indices('/').any(|(at, _)| paths.contains(&relative[..=at]))
+            })
+        })
+        .collect()
 }
 
-fn search_file_contents(
-    files: &[String],
-    query: &str,
+// Re-collect the files (and --git-diff changes) and drop cached contents so
+// the next search sees the tree as it is now. A failed listing keeps the
+// old files.
+fn reload(
+    files: &mut Vec<String>,
     content_cache: &mut LruCache<String, String>,
-    terminal_width: usize,
-) -> Vec<(String, String, Vec<(usize, usize)>)> {
-    if query.is_empty() {
-        return files
-            .iter()
-            .map(|f| (f.clone(), "".to_string(), vec![]))
-            .collect();
-    }
-
-    let re = match RegexBuilder::new(query)
-        .case_insensitive(true)
-        .build()
-    {
-        Ok(regex) => regex,
-        Err(_) => {
-            return vec![("".to_string(), "Invalid regex pattern".to_string(), vec![])];
+    status: &mut Option<String>,
+    source_job: &mut Option<SourceJob>,
+    extensions: &[String],
+    options: &mut Options,
+) {
+    if options.from_json || options.stdin {
+        *status = Some("stdin can't be reloaded".to_string());
+        return;
+    }
+    // The command runs on a worker; the main loop takes its output
+    if let Some(command) = &options.exec_source {
+        match source_job {
+            Some((_, announce)) => *announce = true,
+            None => *source_job = Some((exec::capture_in_background(command), true)),
         }
-    };
+        *status = Some("re-running...".to_string());
+        return;
+    }
+    if options.git_diff {
+        options.changed_lines = git::changed_lines().ok();
+    }
+    *status = Some(match collect_files(extensions, options) {
+        Ok(fresh) => {
+            *files = fresh;
+            content_cache.clear();
+            format!("reloaded ({} files)", files.len())
+        }
+        Err(e) => e,
+    });
+}
 
-    let mut matches = Vec::new();
+// "bundle.zip!inner/file.rs" paths for the text entries of an archive. An
+// unreadable archive just contributes nothing.
+fn archive_text_entries(entry: &DirEntry, extensions: &[String], options: &Options) -> Vec<String> {
+    let Some(path) = entry.path().to_str() else {
+        return Vec::new();
+    };
+    if !entry.metadata().is_ok_and(|meta| passes_file_filters(&meta, options)) {
+        return Vec::new();
+    }
+    archive::list_entries(entry.path())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| format!("{}{}{}", path, archive::SEPARATOR, name))
+        .filter(|composite| is_text_file(Path::new(composite), extensions))
+        .collect()
+}
 
-    for file in files {
-        let content = if let Some(content) = content_cache.get(file) {
-            content.clone()
-        } else {
-            match fs::read_to_string(file) {
-                Ok(content) => {
-                    content_cache.put(file.clone(), content.clone());
-                    content
-                }
-                Err(_) => continue,
-            }
+// With --shebang, a file is wanted for its first line rather than its
+// extension (and archives aren't opened)
+fn collect_text_files(extensions: &[String], options: &Options, shebang: Option<&Regex>) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    let roots = if options.search_roots.is_empty() { vec![".".to_string()] } else { options.search_roots.clone() };
+    // Following links, or walking overlapping --path roots, one file can be
+    // reached by several paths; the first one walked stands for it
+    let dedupe = options.follow_symlinks || roots.len() > 1;
+    for entry in roots.iter().flat_map(|root| {
+        // Each directory's entries in natural order, so a browse of the
+        // files lists file2 before file10
+        WalkDir::new(root)
+            .sort_by(|a, b| natural::compare(&a.file_name().to_string_lossy(), &b.file_name().to_string_lossy()))
+            .follow_links(options.follow_symlinks)
+            .into_iter()
+            .filter_entry(is_not_hidden)
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file())
+    }) {
+        let path = entry.path();
+        if dedupe
+            && let Ok(real) = fs::canonicalize(path)
+            && !seen.insert(real)
+        {
+            continue;
+        }
+        if options.search_archives && shebang.is_none() && archive::is_archive(path) {
+            files.extend(archive_text_entries(&entry, extensions, options));
+            continue;
+        }
+        let wanted = match shebang {
+            Some(shebang) => first_line_matches(path, shebang),
+            None => is_text_file(path, extensions) || (options.binary && is_small_binary(&entry)),
         };
+        if wanted
+            && entry.metadata().is_ok_and(|meta| passes_file_filters(&meta, options))
+            && let Some(path_str) = path.to_str()
+        {
+            files.push(path_str.to_string());
+        }
+    }
+    files
+}
 
-        for line in content.lines() {
-            let mut match_ranges = vec![];
-            let mut first_match_start = None;
-            for mat in re.find_iter(line) {
-                if first_match_start.is_none() {
-                    first_match_start = Some(mat.start());
-                }
-                match_ranges.push((mat.start(), mat.end()));
-            }
-            if !match_ranges.is_empty() {
-                // Initialize truncation variables
-                let max_text_len = terminal_width.saturating_sub(33); // 30 for path + 3 for padding
-                let start_pos;
-                let prefix_offset;
-                let matched_line = if line.len() > max_text_len {
-                    let start = first_match_start.unwrap_or(0);
-                    let context = 20.min(start); // Up to 20 chars before match
-                    start_pos = start.saturating_sub(context);
-                    let end_pos = (start_pos + max_text_len).min(line.len());
-                    let mut truncated = line[start_pos..end_pos].to_string();
-                    prefix_offset = if start_pos > 0 {
-                        truncated = format!("...{}", truncated);
-                        3 // Account for "..."
-                    } else {
-                        0
-                    };
-                    if end_pos < line.len() {
-                        truncated.push_str("...");
-                    }
-                    truncated
-                } else {
-                    start_pos = 0;
-                    prefix_offset = 0;
-                    line.to_string()
-                };
+// Filters on file metadata that apply to walked and git-tracked files alike
+fn passes_file_filters(meta: &fs::Metadata, options: &Options) -> bool {
+    if let Some(since) = options.modified_since
+        && !meta.modified().is_ok_and(|modified| modified >= since)
+    {
+        return false;
+    }
+    options.min_size.is_none_or(|min| meta.len() >= min) && options.max_size.is_none_or(|max| meta.len() <= max)
+}
 
-                // Adjust match ranges for truncated line
-                let adjusted_ranges = match_ranges
-                    .into_iter()
-                    .filter(|&(start, _)| start >= start_pos) // Include ranges after start_pos
-                    .map(|(start, end)| {
-                        let new_start = start - start_pos + prefix_offset;
-                        let new_end = end - start_pos + prefix_offset;
-                        (new_start, new_end.min(matched_line.len()))
-                    })
-                    .filter(|&(start, end)| start < matched_line.len() && end <= matched_line.len())
-                    .collect::<Vec<(usize, usize)>>();
-
-                matches.push((file.clone(), matched_line, adjusted_ranges));
-            }
-        }
+// With --binary, files of other types are searched too, up to this size
+const BINARY_SIZE_CAP: u64 = 8 * 1024 * 1024;
+
+fn is_small_binary(entry: &DirEntry) -> bool {
+    entry
+        .metadata()
+        .map(|meta| meta.len() <= BINARY_SIZE_CAP)
+        .unwrap_or(false)
+}
+
+// Tracked files as listed by git, falling back to the normal walk outside a
+// repository. Paths get the same "./" prefix the walk produces.
+fn collect_tracked_files(extensions: &[String], options: &Options, shebang: Option<&Regex>) -> Vec<String> {
+    match git::tracked_files() {
+        Ok(tracked) => tracked
+            .into_iter()
+            .map(|path| format!("./{}", path))
+            .filter(|path| shebang.is_some() || is_text_file(Path::new(path), extensions))
+            .filter(|path| fs::metadata(path).is_ok_and(|meta| meta.is_file() && passes_file_filters(&meta, options)))
+            .filter(|path| shebang.is_none_or(|shebang| first_line_matches(Path::new(path), shebang)))
+            .collect(),
+        Err(_) => collect_text_files(extensions, options, shebang),
     }
+}
+
+// A first line longer than this is cut here before --shebang matches it
+const FIRST_LINE_CAP: u64 = 1024;
+
+// Whether --shebang matches the first line of `path`, which is all that
+// is read of it
+fn first_line_matches(path: &Path, shebang: &Regex) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return false;
+    };
+    let mut line = Vec::new();
+    if std::io::BufReader::new(file.take(FIRST_LINE_CAP)).read_until(b'\n', &mut line).is_err() {
+        return false;
+    }
+    shebang.is_match(String::from_utf8_lossy(&line).trim_end())
+}
+
+fn collect_revision_files(rev: &str, extensions: &[String]) -> std::io::Result<Vec<String>> {
+    Ok(git::list_files(rev)?
+        .into_iter()
+        .filter(|path| is_text_file(Path::new(path), extensions))
+        .collect())
+}
+
+// Path colors by extension, in the spirit of `ls --color`
+const EXTENSION_COLORS: &[(&str, Color)] = &[
+    ("rs", Color::AnsiValue(208)),
+    ("md", Color::Green),
+    ("txt", Color::Grey),
+    ("py", Color::Yellow),
+    ("js", Color::DarkYellow),
+    ("ts", Color::Blue),
+    ("html", Color::DarkRed),
+    ("css", Color::DarkMagenta),
+    ("json", Color::DarkGreen),
+    ("yaml", Color::DarkGreen),
+    ("yml", Color::DarkGreen),
+    ("toml", Color::DarkGreen),
+    ("ini", Color::DarkGreen),
+    ("sh", Color::Red),
+    ("bash", Color::Red),
+    ("c", Color::DarkBlue),
+    ("h", Color::DarkBlue),
+    ("cpp", Color::DarkBlue),
+    ("java", Color::DarkCyan),
+    ("go", Color::Cyan),
+    ("rb", Color::DarkRed),
+    ("php", Color::Magenta),
+    ("sql", Color::DarkYellow),
+];
 
-    matches
+fn path_color(file: &str, options: &Options) -> Color {
+    Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| {
+            let ext = ext.to_lowercase();
+            options
+                .ext_colors
+                .iter()
+                .find(|(e, _)| *e == ext)
+                .map(|&(_, color)| color)
+                .or_else(|| EXTENSION_COLORS.iter().find(|(e, _)| *e == ext).map(|&(_, color)| color))
+        })
+        .unwrap_or(Color::White)
 }
+
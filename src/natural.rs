@@ -0,0 +1,37 @@
+use std::cmp::Ordering;
+
+// Natural order, as natord has it: runs of digits compare by their value,
+// so file2 comes before file10 and v1.9 before v1.10, and everything else
+// compares character by character. Numbers equal in value (007 and 7) are
+// told apart by their length, shorter first, so no two strings tie unless
+// they are the same.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    let mut tie = Ordering::Equal;
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len()).then(tie);
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (x_digits, x_rest) = split_number(a);
+            let (y_digits, y_rest) = split_number(b);
+            let (x_value, y_value) = (x_digits.trim_start_matches('0'), y_digits.trim_start_matches('0'));
+            let order = x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value));
+            if order != Ordering::Equal {
+                return order;
+            }
+            tie = tie.then(x_digits.len().cmp(&y_digits.len()));
+            (a, b) = (x_rest, y_rest);
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+// "123abc" -> ("123", "abc")
+fn split_number(text: &str) -> (&str, &str) {
+    text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()))
+}
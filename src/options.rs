@@ -0,0 +1,710 @@
+use crate::completions;
+use crate::config::{self, Config};
+use crate::dates;
+use crate::git;
+use crate::keys::KeyBinding;
+use crate::patterns;
+use crate::severity;
+use crossterm::style::Color;
+use std::env;
+use std::time::SystemTime;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    // Directory walk order
+    #[default]
+    Walk,
+    // Match count boosted by how recently the file was modified
+    Recency,
+    // By path, a file's lines staying in order
+    Path,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // Preview to the right of the results
+    #[default]
+    Horizontal,
+    // Preview below the results
+    Vertical,
+}
+
+// How matches stand out from the rest of a row. Every style keeps the
+// per-pattern colors; the others add a text attribute on top for terminals
+// or readers that can't rely on color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightStyle {
+    #[default]
+    Color,
+    Bold,
+    Underline,
+    Reverse,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    #[default]
+    Insensitive,
+    Sensitive,
+    // Insensitive unless the pattern contains an uppercase letter
+    Smart,
+}
+
+impl Case {
+    pub fn ignores_case(self, pattern: &str) -> bool {
+        match self {
+            Case::Insensitive => true,
+            Case::Sensitive => false,
+            Case::Smart => !pattern.chars().any(char::is_uppercase),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    // Collapse result rows with identical matched text, keeping the first
+    pub unique: bool,
+    // Match words ignoring punctuation and spacing (the query is not a regex)
+    pub loose: bool,
+    // Take the query and --pattern as plain text rather than regexes
+    pub literal: bool,
+    // --regex: regexes even where the config makes literal the default
+    pub regex: bool,
+    // Compose decomposed characters (e + combining accent) before matching
+    pub normalize: bool,
+    // Search the files of this git revision instead of the working tree
+    pub rev: Option<String>,
+    // Search only files tracked by git instead of walking the directory
+    pub tracked_only: bool,
+    // Leave out the walked files that git ignores
+    pub gitignore: bool,
+    // Search the files whose first line matches this regex, whatever their extension
+    pub shebang: Option<String>,
+    // Only match lines added or changed since HEAD
+    pub git_diff: bool,
+    // The changes --git-diff found, filled in by main rather than a flag.
+    // None outside a git repository, where nothing is restricted.
+    pub changed_lines: Option<git::ChangedLines>,
+    // Walk into symlinked directories, searching each real file once
+    pub follow_symlinks: bool,
+    // Color and sort weight of each --pattern that is an annotation word
+    // like TODO, filled in by main from the defaults and config file
+    pub severities: Vec<Option<severity::Severity>>,
+    // Background of the selected result's line in the preview, filled in
+    // by main from the default and config file
+    pub preview_focus: Option<Color>,
+    // Path colors by extension from the config file, ahead of the built-in
+    // ones; filled in by main
+    pub ext_colors: Vec<(String, Color)>,
+    // Keep the "./" the directory walk puts in front of every path
+    pub dot_prefix: bool,
+    // Cut long paths in the middle, keeping the first directory and the
+    // file name, rather than at the start
+    pub elide_middle: bool,
+    // Also search non-text files (up to a size cap), decoded as Latin-1
+    pub binary: bool,
+    // Count match columns in bytes rather than chars
+    pub byte_offsets: bool,
+    // Also search the text entries of .zip and .tar archives found by the walk
+    pub search_archives: bool,
+    // Only search files modified at or after this time
+    pub modified_since: Option<SystemTime>,
+    // Only search files of at least / at most this many bytes
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    // Match the query against file paths instead of file contents
+    pub paths: bool,
+    // Interpret the path query as a glob rather than a regex (implies `paths`)
+    pub glob: bool,
+    // Match paths fuzzily (query characters in order) and rank them
+    pub fuzzy: bool,
+    // Case matching for --paths and --glob, independent of content search
+    pub path_case: Case,
+    // Run this query once and print the results instead of starting the UI
+    pub query: Option<String>,
+    // Extra patterns searched alongside the query, each highlighted in its own color
+    pub patterns: Vec<String>,
+    // Print non-interactive results as JSON lines
+    pub json: bool,
+    // With --query, send the output through $PAGER when it goes to a terminal
+    pub pager: bool,
+    // With --query, print results as a Markdown list grouped by file
+    pub markdown: bool,
+    // Show results grouped under a foldable tree of directories and files
+    pub tree: bool,
+    // Search the text of --json records read from stdin instead of files
+    pub from_json: bool,
+    // Search the text piped to stdin as one document instead of files
+    pub stdin: bool,
+    // Search this shell command's output as one document instead of files
+    pub exec_source: Option<String>,
+    // Re-run the search when a searched file changes on disk
+    pub watch: bool,
+    // Search and redraw on every pass of the UI loop, for profiling
+    pub always_redraw: bool,
+    // Answer JSON search requests on this Unix socket instead of running the UI
+    pub serve: Option<String>,
+    // Named pipe whose lines replace the query of the running UI
+    pub query_fifo: Option<String>,
+    // Result row template with {path}, {line} and {text} fields
+    pub format: Option<String>,
+    // Wrap long matched lines onto extra rows instead of truncating them
+    pub wrap: bool,
+    // A result row for each match, showing only the matched text
+    pub only_matching: bool,
+    // Only keep matches inside comments and/or string literals
+    pub in_comments: bool,
+    pub in_strings: bool,
+    // Drop result lines that also match any of these (--not)
+    pub excludes: Vec<String>,
+    // Skip lines without this literal before running the regexes
+    pub prefilter: Option<String>,
+    // Cap on the shown width of matched text, terminal or not
+    pub max_columns: Option<usize>,
+    // Match each line joined with the lines after it, N lines in all
+    pub window: Option<usize>,
+    // Match only within this (1-based) field of each line, split on `delimiter`
+    pub field: Option<usize>,
+    pub delimiter: Option<char>,
+    // Only search lines START to END (1-based, inclusive) of each file
+    pub range: Option<(usize, usize)>,
+    // Leave the \r of \r\n line endings on lines instead of dropping it
+    pub keep_cr: bool,
+    // Only match lines that a pattern matches from start to end
+    pub line_regexp: bool,
+    // Report how many files, lines and bytes each search read
+    pub stats: bool,
+    // Show a " ×N" badge on rows whose line matched more than once
+    pub show_match_counts: bool,
+    // Shade the row under the prompt by where the matches are in the list
+    pub heatmap: bool,
+    // Start with the prompt hidden and the results filling the screen
+    pub results_only: bool,
+    // Show the values of the query's named capture groups with each result
+    pub show_captures: bool,
+    // Grey out matched-line text that is not near a match
+    pub dim_context: bool,
+    // Drop leading whitespace from matched lines
+    pub trim: bool,
+    // Show runs of spaces and tabs in matched lines as one space
+    pub collapse_whitespace: bool,
+    // Show how long ago each result's file was modified
+    pub show_age: bool,
+    // Mark trailing whitespace and draw tabs and other invisibles as symbols
+    pub show_whitespace: bool,
+    // Show the lines around the selected result in a pane beside the list
+    pub preview: bool,
+    // Color the preview by the file's language
+    pub syntax_highlight: bool,
+    // Where the preview goes relative to the results
+    pub layout: Layout,
+    // Lines shown either side of the match in the preview, instead of
+    // filling the pane
+    pub context: Option<usize>,
+    // Emphasis for matched text in result rows
+    pub highlight_style: HighlightStyle,
+    // Extensions to search instead of the built-in list
+    pub extensions: Option<Vec<String>>,
+    // Extensions to search in addition to the built-in (or --extensions) list
+    pub add_extensions: Vec<String>,
+    // --map-ext from=to pairs: files ending in `from` are treated as `to`
+    pub ext_map: Vec<(String, String)>,
+    // Extra default extensions from TERMISCOPE_EXTENSIONS, ignored with --extensions
+    pub env_extensions: Vec<String>,
+    // Order of results across files
+    pub sort: SortOrder,
+    // Compare numbers in paths by value for --sort path (file2 before file10)
+    pub natural_sort: bool,
+    // Overrides rank::DEFAULT_RECENCY_WEIGHT for --sort recency
+    pub recency_weight: Option<f64>,
+    // Shell command run on the selected result; `{}` is the path, `{line}` the line
+    pub exec: Option<String>,
+    // Key that runs `exec`, DEFAULT_EXEC_KEY if unset
+    pub exec_key: Option<KeyBinding>,
+    // Print the resolved settings and exit
+    pub show_config: bool,
+    // Print the files that would be searched, one per line, and exit
+    pub list_files: bool,
+    // Stop searching after this long and show what was found so far
+    pub timeout_ms: Option<u64>,
+    // Give up on a single file after this many milliseconds
+    pub file_timeout_ms: Option<u64>,
+    // Worker threads for content search, one per core when unset or 0
+    pub threads: Option<usize>,
+    // Query the interactive prompt starts with, from --pattern-file or --named
+    pub initial_query: Option<String>,
+    // Split contents on this instead of newlines; results then count records
+    pub record_separator: Option<String>,
+    // Bookmark name (or directory) to search instead of the current directory
+    pub root: Option<String>,
+    // Directories walked instead of the current one, from --path
+    pub search_roots: Vec<String>,
+    // Save the current directory as this bookmark and exit
+    pub save_root: Option<String>,
+    // Print the completion script for this shell and exit
+    pub generate_completions: Option<String>,
+}
+
+pub const DEFAULT_EXEC_KEY: KeyBinding = KeyBinding::ctrl('x');
+
+impl Options {
+    pub fn from_args() -> Result<Options, String> {
+        let mut options = Self::parse(expand_aliases(env::args().skip(1).collect())?)?;
+        if let Ok(list) = env::var("TERMISCOPE_EXTENSIONS") {
+            options.env_extensions = extension_list(&list);
+        }
+        Ok(options)
+    }
+
+    // The extension `ext` is treated as after --map-ext, lowercased
+    pub fn mapped_extension(&self, ext: &str) -> String {
+        let ext = ext.to_lowercase();
+        self.ext_map
+            .iter()
+            .rev()
+            .find(|(from, _)| *from == ext)
+            .map_or(ext, |(_, to)| to.clone())
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--unique" => options.unique = true,
+                "--loose" => options.loose = true,
+                "--literal" | "-F" => options.literal = true,
+                "--regex" => options.regex = true,
+                "--normalize" => options.normalize = true,
+                "--line-regexp" | "-x" => options.line_regexp = true,
+                "--only-matching" | "-o" => options.only_matching = true,
+                "--watch" => options.watch = true,
+                "--always-redraw" => options.always_redraw = true,
+                "--tree" => options.tree = true,
+                "--in-comments" => options.in_comments = true,
+                "--in-strings" => options.in_strings = true,
+                "--keep-cr" => options.keep_cr = true,
+                "--show-config" => options.show_config = true,
+                "--list-files" => options.list_files = true,
+                "--paths" => options.paths = true,
+                "--glob" => {
+                    options.paths = true;
+                    options.glob = true;
+                }
+                "--fuzzy" => {
+                    options.paths = true;
+                    options.fuzzy = true;
+                }
+                "--path-case" => {
+                    options.path_case = match value(&mut args, &arg)?.as_str() {
+                        "insensitive" => Case::Insensitive,
+                        "sensitive" => Case::Sensitive,
+                        "smart" => Case::Smart,
+                        other => return Err(format!("unknown path case: {}", other)),
+                    }
+                }
+                "--query" => options.query = Some(value(&mut args, &arg)?),
+                "--pattern-file" => options.initial_query = Some(patterns::from_file(&value(&mut args, &arg)?)?),
+                "--named" => options.initial_query = Some(patterns::named(&value(&mut args, &arg)?)?),
+                "--json" => options.json = true,
+                "--markdown" => options.markdown = true,
+                "--from-json" => options.from_json = true,
+                "--pager" => options.pager = true,
+                "--stdin" => options.stdin = true,
+                "--exec-source" => options.exec_source = Some(value(&mut args, &arg)?),
+                "--search-archives" => options.search_archives = true,
+                "--serve" => options.serve = Some(value(&mut args, &arg)?),
+                "--format" => options.format = Some(value(&mut args, &arg)?),
+                "--pattern" => options.patterns.push(value(&mut args, &arg)?),
+                "--not" => options.excludes.push(value(&mut args, &arg)?),
+                "--prefilter" => options.prefilter = Some(value(&mut args, &arg)?),
+                "--wrap" => options.wrap = true,
+                "--show-match-counts" => options.show_match_counts = true,
+                "--heatmap" => options.heatmap = true,
+                "--results-only" => options.results_only = true,
+                "--stats" => options.stats = true,
+                "--show-captures" => options.show_captures = true,
+                "--dim-context" => options.dim_context = true,
+                "--trim" => options.trim = true,
+                "--collapse-whitespace" => options.collapse_whitespace = true,
+                "--show-age" => options.show_age = true,
+                "--show-whitespace" => options.show_whitespace = true,
+                "--preview" => options.preview = true,
+                "--layout" => {
+                    options.layout = match value(&mut args, &arg)?.as_str() {
+                        "horizontal" => Layout::Horizontal,
+                        "vertical" => Layout::Vertical,
+                        other => return Err(format!("unknown layout: {}", other)),
+                    }
+                }
+                "--highlight-style" => {
+                    options.highlight_style = match value(&mut args, &arg)?.as_str() {
+                        "color" => HighlightStyle::Color,
+                        "bold" => HighlightStyle::Bold,
+                        "underline" => HighlightStyle::Underline,
+                        "reverse" => HighlightStyle::Reverse,
+                        other => return Err(format!("unknown highlight style: {}", other)),
+                    }
+                }
+                "--syntax-highlight" => {
+                    options.preview = true;
+                    options.syntax_highlight = true;
+                }
+                "--extensions" => options.extensions = Some(extension_list(&value(&mut args, &arg)?)),
+                "--map-ext" => {
+                    let pair = value(&mut args, &arg)?;
+                    let Some((from, to)) = pair.split_once('=') else {
+                        return Err(format!("invalid extension mapping: {} (expected from=to)", pair));
+                    };
+                    let normalize = |ext: &str| ext.trim().trim_start_matches('.').to_lowercase();
+                    options.ext_map.push((normalize(from), normalize(to)));
+                }
+                "--add-extensions" => options
+                    .add_extensions
+                    .extend(extension_list(&value(&mut args, &arg)?)),
+                "--natural-sort" => options.natural_sort = true,
+                "--sort" => {
+                    options.sort = match value(&mut args, &arg)?.as_str() {
+                        "walk" => SortOrder::Walk,
+                        "recency" => SortOrder::Recency,
+                        "path" => SortOrder::Path,
+                        other => return Err(format!("unknown sort order: {}", other)),
+                    }
+                }
+                "--recency-weight" => {
+                    let weight = value(&mut args, &arg)?;
+                    options.recency_weight = Some(
+                        weight
+                            .parse()
+                            .map_err(|_| format!("invalid recency weight: {}", weight))?,
+                    );
+                }
+                "--exec" => options.exec = Some(value(&mut args, &arg)?),
+                "--exec-key" => options.exec_key = Some(KeyBinding::parse(&value(&mut args, &arg)?)?),
+                "--tracked-only" => options.tracked_only = true,
+                "--gitignore" => options.gitignore = true,
+                "--shebang" => options.shebang = Some(value(&mut args, &arg)?),
+                "--dot-prefix" => options.dot_prefix = true,
+                "--elide-middle" => options.elide_middle = true,
+                "--follow-symlinks" => options.follow_symlinks = true,
+                "--git-diff" => options.git_diff = true,
+                "--binary" => options.binary = true,
+                "--byte-offsets" => options.byte_offsets = true,
+                "--modified-since" => options.modified_since = Some(dates::parse_date(&value(&mut args, &arg)?)?),
+                "--modified-within" => {
+                    let text = value(&mut args, &arg)?;
+                    let within = dates::parse_duration(&text)?;
+                    let since = SystemTime::now().checked_sub(within);
+                    options.modified_since = Some(since.ok_or_else(|| format!("--modified-within {} reaches too far back", text))?);
+                }
+                "--min-size" => options.min_size = Some(size(&value(&mut args, &arg)?)?),
+                "--max-size" => options.max_size = Some(size(&value(&mut args, &arg)?)?),
+                "--empty" => options.max_size = Some(0),
+                "--non-empty" => options.min_size = Some(1),
+                "--timeout-ms" => {
+                    let ms = value(&mut args, &arg)?;
+                    options.timeout_ms = Some(ms.parse().map_err(|_| format!("invalid timeout: {}", ms))?);
+                }
+                "--file-timeout-ms" => {
+                    let ms = value(&mut args, &arg)?;
+                    options.file_timeout_ms = Some(ms.parse().map_err(|_| format!("invalid timeout: {}", ms))?);
+                }
+                "--max-columns" | "-M" => {
+                    let n = value(&mut args, &arg)?;
+                    options.max_columns = Some(n.parse().map_err(|_| format!("invalid column count: {}", n))?);
+                }
+                "--window" => {
+                    let n = value(&mut args, &arg)?;
+                    options.window = Some(n.parse().map_err(|_| format!("invalid window: {}", n))?);
+                }
+                "--range" => options.range = Some(range(&value(&mut args, &arg)?)?),
+                "--context" => {
+                    let n = value(&mut args, &arg)?;
+                    options.context = Some(n.parse().map_err(|_| format!("invalid context: {}", n))?);
+                }
+                "--threads" => {
+                    let n = value(&mut args, &arg)?;
+                    options.threads = Some(n.parse().map_err(|_| format!("invalid thread count: {}", n))?);
+                }
+                "--field" => {
+                    let n = value(&mut args, &arg)?;
+                    options.field = Some(n.parse().ok().filter(|&n| n > 0).ok_or_else(|| format!("invalid field: {}", n))?);
+                }
+                "--delimiter" => options.delimiter = Some(delimiter(&value(&mut args, &arg)?)?),
+                "--record-separator" => {
+                    options.record_separator = Some(record_separator(&value(&mut args, &arg)?)?)
+                }
+                "--root" => options.root = Some(value(&mut args, &arg)?),
+                "--query-fifo" => options.query_fifo = Some(value(&mut args, &arg)?),
+                "--path" => {
+                    // Walked paths are the root joined with what is below it,
+                    // so a trailing slash would show up doubled
+                    let dir = config::expand_home(&value(&mut args, &arg)?).display().to_string();
+                    let dir = match dir.trim_end_matches('/') {
+                        "" => "/".to_string(),
+                        trimmed => trimmed.to_string(),
+                    };
+                    options.search_roots.push(dir);
+                }
+                "--save-root" => options.save_root = Some(value(&mut args, &arg)?),
+                "--rev" => options.rev = Some(value(&mut args, &arg)?),
+                "--generate-completions" => {
+                    let shell = value(&mut args, &arg)?;
+                    if !completions::SHELLS.contains(&shell.as_str()) {
+                        return Err(format!("unknown shell: {} (expected {})", shell, completions::SHELLS.join(", ")));
+                    }
+                    options.generate_completions = Some(shell);
+                }
+                other => return Err(format!("unknown option: {}", other)),
+            }
+        }
+        // Both read stdin, and a server has no way to reload piped text
+        if options.stdin && (options.from_json || options.serve.is_some()) {
+            return Err("--stdin can't be combined with --from-json or --serve".to_string());
+        }
+        if options.watch && (options.stdin || options.from_json || options.rev.is_some()) {
+            return Err("--watch needs files on disk: it can't be combined with --stdin, --from-json or --rev".to_string());
+        }
+        if options.exec_source.is_some()
+            && (options.stdin
+                || options.from_json
+                || options.rev.is_some()
+                || options.tracked_only
+                || options.git_diff
+                || !options.search_roots.is_empty())
+        {
+            return Err(
+                "--exec-source can't be combined with --stdin, --from-json, --rev, --tracked-only, --git-diff or --path"
+                    .to_string(),
+            );
+        }
+        if options.window.is_some() && options.record_separator.is_some() {
+            return Err("--window can't be combined with --record-separator".to_string());
+        }
+        if options.delimiter.is_some() && options.field.is_none() {
+            return Err("--delimiter needs --field".to_string());
+        }
+        // A window's later lines have fields of their own
+        if options.field.is_some() && options.window.is_some() {
+            return Err("--field can't be combined with --window".to_string());
+        }
+        // A revision's files aren't on disk to read first lines from
+        if options.shebang.is_some() && options.rev.is_some() {
+            return Err("--shebang can't be combined with --rev".to_string());
+        }
+        if options.literal && options.regex {
+            return Err("--literal can't be combined with --regex".to_string());
+        }
+        if options.normalize && options.loose {
+            return Err("--normalize can't be combined with --loose".to_string());
+        }
+        // Changed lines are line numbers in working tree files
+        if options.git_diff
+            && (options.rev.is_some() || options.record_separator.is_some() || options.from_json || options.stdin)
+        {
+            return Err("--git-diff can't be combined with --rev, --record-separator, --from-json or --stdin".to_string());
+        }
+        // Records and piped or command text are not files
+        if options.list_files && (options.stdin || options.from_json || options.exec_source.is_some()) {
+            return Err("--list-files can't be combined with --stdin, --from-json or --exec-source".to_string());
+        }
+        // The other sources list files of the current directory (or none)
+        if !options.search_roots.is_empty()
+            && (options.tracked_only || options.git_diff || options.rev.is_some() || options.stdin || options.from_json)
+        {
+            return Err("--path can't be combined with --tracked-only, --git-diff, --rev, --stdin or --from-json".to_string());
+        }
+        Ok(options)
+    }
+}
+
+// Replace each `@name` argument that names an alias in the config file
+// with the arguments it stands for, which may use other aliases. Other
+// `@` arguments are left alone. The config is only read if an argument
+// starts with `@`.
+fn expand_aliases(args: Vec<String>) -> Result<Vec<String>, String> {
+    if !args.iter().any(|arg| arg.starts_with('@')) {
+        return Ok(args);
+    }
+    let config = config::load()?;
+    let mut expanded = Vec::new();
+    splice_aliases(args, &config, &mut vec![], &mut expanded)?;
+    Ok(expanded)
+}
+
+// `active` holds the aliases being expanded, outermost first, to catch
+// one that ends up using itself
+fn splice_aliases(args: Vec<String>, config: &Config, active: &mut Vec<String>, out: &mut Vec<String>) -> Result<(), String> {
+    for arg in args {
+        let Some(words) = arg.strip_prefix('@').and_then(|name| config.alias(name)) else {
+            out.push(arg);
+            continue;
+        };
+        let name = arg[1..].to_string();
+        if active.contains(&name) {
+            let chain: Vec<String> = active.iter().chain([&name]).map(|name| format!("@{}", name)).collect();
+            return Err(format!("alias loop: {}", chain.join(" -> ")));
+        }
+        active.push(name);
+        splice_aliases(words, config, active, out)?;
+        active.pop();
+    }
+    Ok(())
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} requires a value", flag))
+}
+
+// One character, or `tab` (also written \t) for TSV
+fn delimiter(spec: &str) -> Result<char, String> {
+    let spec = if spec == "tab" { "\\t" } else { spec };
+    let expanded = record_separator(spec)?;
+    let mut chars = expanded.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("invalid delimiter: {} (expected a single character)", spec)),
+    }
+}
+
+// "blank" and "nul" name the common separators; anything else is taken
+// literally after expanding \n, \t, \0 and \\
+fn record_separator(spec: &str) -> Result<String, String> {
+    let separator = match spec {
+        "blank" => "\n\n".to_string(),
+        "nul" => "\0".to_string(),
+        _ => {
+            let mut separator = String::new();
+            let mut chars = spec.chars();
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    separator.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('n') => separator.push('\n'),
+                    Some('t') => separator.push('\t'),
+                    Some('0') => separator.push('\0'),
+                    Some('\\') | None => separator.push('\\'),
+                    Some(other) => {
+                        separator.push('\\');
+                        separator.push(other);
+                    }
+                }
+            }
+            separator
+        }
+    };
+    if separator.is_empty() {
+        return Err("record separator cannot be empty".to_string());
+    }
+    Ok(separator)
+}
+
+// A byte count with an optional K, M or G suffix (powers of 1024): "512",
+// "64K", "1.5M"
+fn size(spec: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size: {} (expected e.g. 512, 64K, 10M or 1G)", spec);
+    let upper = spec.trim().to_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, unit) = match digits.char_indices().last() {
+        Some((at, 'K')) => (&digits[..at], 1u64 << 10),
+        Some((at, 'M')) => (&digits[..at], 1 << 20),
+        Some((at, 'G')) => (&digits[..at], 1 << 30),
+        _ => (digits, 1),
+    };
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let bytes = (number * unit as f64).round();
+    // `as` would saturate a size too large to count at u64::MAX
+    if !bytes.is_finite() || number < 0.0 || bytes >= u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+// "1000:2000", "1000:" (to the end) or ":2000" (from the start), line
+// numbers counted from 1 with both ends included
+fn range(spec: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid range: {} (expected START:END, e.g. 1000:2000, 1000: or :2000)", spec);
+    let (start, end) = spec.split_once(':').ok_or_else(invalid)?;
+    let bound = |text: &str, open: usize| match text.trim() {
+        "" => Ok(open),
+        text => text.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(invalid),
+    };
+    let (start, end) = (bound(start, 1)?, bound(end, usize::MAX)?);
+    if start > end {
+        return Err(format!("invalid range: {} (starts after it ends)", spec));
+    }
+    Ok((start, end))
+}
+
+// "rs,.Vue, md" -> ["rs", "vue", "md"]
+fn extension_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_suffixes() {
+        assert_eq!(size("512"), Ok(512));
+        assert_eq!(size("64K"), Ok(64 * 1024));
+        assert_eq!(size("64k"), Ok(64 * 1024));
+        assert_eq!(size("10MB"), Ok(10 * 1024 * 1024));
+        assert_eq!(size("1.5M"), Ok(3 * 512 * 1024));
+        assert_eq!(size("1G"), Ok(1 << 30));
+        assert_eq!(size(" 2 K "), Ok(2048));
+    }
+
+    #[test]
+    fn size_zero() {
+        assert_eq!(size("0"), Ok(0));
+        assert_eq!(size("0K"), Ok(0));
+    }
+
+    #[test]
+    fn size_overflow() {
+        assert!(size("99999999999999999999").is_err());
+        assert!(size("17179869184G").is_err());
+        assert_eq!(size("17179869183G"), Ok(17179869183 << 30));
+    }
+
+    #[test]
+    fn size_garbage() {
+        for spec in ["", "K", "abc", "12Q", "-1", "-1K", "1..5M", "inf", "NaN", "1KM"] {
+            assert!(size(spec).is_err(), "{:?} was accepted", spec);
+        }
+    }
+
+    #[test]
+    fn range_bounds() {
+        assert_eq!(range("1000:2000"), Ok((1000, 2000)));
+        assert_eq!(range("5:5"), Ok((5, 5)));
+        assert_eq!(range(" 3 : 7 "), Ok((3, 7)));
+    }
+
+    #[test]
+    fn range_open_ends() {
+        assert_eq!(range("1000:"), Ok((1000, usize::MAX)));
+        assert_eq!(range(":2000"), Ok((1, 2000)));
+        assert_eq!(range(":"), Ok((1, usize::MAX)));
+    }
+
+    #[test]
+    fn range_reversed() {
+        assert!(range("2000:1000").is_err_and(|e| e.contains("starts after it ends")));
+    }
+
+    #[test]
+    fn range_garbage() {
+        for spec in ["", "10", "0:5", "1:0", "a:b", "-1:5", "1:2:3"] {
+            assert!(range(spec).is_err(), "{:?} was accepted", spec);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::format::{self, Part};
+use crate::json;
+use crate::search::SearchResult;
+use crate::tree::{Kind, Row, Tree};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+pub fn write_plain<W: Write>(out: &mut W, results: &[SearchResult]) -> io::Result<()> {
+    for result in results {
+        if result.line == 0 {
+            writeln!(out, "{}", result.path)?;
+        } else if let Some(offset) = result.offset {
+            writeln!(out, "{}:{}:{}: {}", result.path, result.line, offset, result.text)?;
+        } else {
+            writeln!(out, "{}:{}: {}", result.path, result.line, result.text)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_formatted<W: Write>(out: &mut W, results: &[SearchResult], parts: &[Part]) -> io::Result<()> {
+    for result in results {
+        writeln!(out, "{}", format::render(parts, result))?;
+    }
+    Ok(())
+}
+
+// One JSON object per line: {"path": ..., "line": ..., "column": ..., "text": ..., "ranges": [[start, end], ...]},
+// plus "offset" for binary matches, "start_line" for a --record-separator
+// record (the file line it starts on) and a "captures" object of named
+// groups with --show-captures
+pub fn write_json<W: Write>(out: &mut W, results: &[SearchResult]) -> io::Result<()> {
+    for result in results {
+        let ranges = result
+            .ranges
+            .iter()
+            .map(|(start, end, _)| format!("[{},{}]", start, end))
+            .collect::<Vec<_>>()
+            .join(",");
+        let offset = result
+            .offset
+            .map(|offset| format!(",\"offset\":{}", offset))
+            .unwrap_or_default();
+        let start_line = result
+            .start_line
+            .map(|line| format!(",\"start_line\":{}", line))
+            .unwrap_or_default();
+        let captures = if result.captures.is_empty() {
+            String::new()
+        } else {
+            let fields = result
+                .captures
+                .iter()
+                .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(",\"captures\":{{{}}}", fields)
+        };
+        writeln!(
+            out,
+            "{{\"path\":{},\"line\":{}{},\"column\":{}{},\"text\":{},\"ranges\":[{}]{}}}",
+            json_string(&result.path),
+            result.line,
+            start_line,
+            result.column,
+            offset,
+            json_string(&result.text),
+            ranges,
+            captures
+        )?;
+    }
+    Ok(())
+}
+
+// A header row, then path, line number and text of each result, quoted
+// where needed as RFC 4180 has it
+pub fn write_csv<W: Write>(out: &mut W, results: &[SearchResult]) -> io::Result<()> {
+    writeln!(out, "path,line,text")?;
+    for result in results {
+        writeln!(out, "{},{},{}", csv_field(&result.path), result.line, csv_field(&result.text))?;
+    }
+    Ok(())
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// A Markdown list with one item per file, each holding a fenced block of
+// its matched lines prefixed by their line numbers
+pub fn write_markdown<W: Write>(out: &mut W, results: &[SearchResult]) -> io::Result<()> {
+    for (index, group) in results.chunk_by(|a, b| a.path == b.path).enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        let path = &group[0].path;
+        writeln!(out, "- {}", inline_code(path))?;
+
+        let lines: Vec<String> = group
+            .iter()
+            .filter(|result| result.line > 0)
+            .map(|result| format!("{}: {}", result.line, result.text))
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let fence = "`".repeat((longest_backtick_run(&lines.concat()) + 1).max(3));
+        let language = Path::new(path).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        writeln!(out)?;
+        writeln!(out, "  {}{}", fence, language)?;
+        for line in &lines {
+            writeln!(out, "  {}", line)?;
+        }
+        writeln!(out, "  {}", fence)?;
+    }
+    Ok(())
+}
+
+// `text` as a Markdown code span, delimited by more backticks than any run
+// inside it (and padded with spaces when it starts or ends with one)
+fn inline_code(text: &str) -> String {
+    let ticks = "`".repeat(longest_backtick_run(text) + 1);
+    let pad = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+    format!("{}{}{}{}{}", ticks, pad, text, pad, ticks)
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+// The --tree rows as text: connectors, then each directory and file with
+// its match count, or a match's line number and text
+pub fn write_tree<W: Write>(out: &mut W, tree: &Tree) -> io::Result<()> {
+    for row in &tree.rows {
+        writeln!(out, "{}", tree_row_text(tree, row))?;
+    }
+    Ok(())
+}
+
+pub fn tree_row_text(tree: &Tree, row: &Row) -> String {
+    match &row.kind {
+        Kind::Node { name, count, .. } => format!("{}{} ({})", row.prefix, name, count),
+        Kind::Match(index) => {
+            let result = &tree.results[*index];
+            format!("{}{}: {}", row.prefix, result.line, result.text)
+        }
+    }
+}
+
+// Records written by --json, one per line, for --from-json. Blank lines
+// are skipped; anything else that is not a record is an error.
+pub fn read_json<R: BufRead>(input: R) -> Result<Vec<SearchResult>, String> {
+    let mut records = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: &str| format!("line {} of input: {}", index + 1, reason);
+        let value = json::parse(&line).map_err(|e| invalid(&e))?;
+        let field = |name| value.get(name).ok_or_else(|| invalid(&format!("missing \"{}\"", name)));
+        records.push(SearchResult {
+            path: field("path")?.as_str().ok_or_else(|| invalid("\"path\" is not a string"))?.to_string(),
+            line: field("line")?.as_usize().ok_or_else(|| invalid("\"line\" is not a line number"))?,
+            start_line: value.get("start_line").and_then(json::Value::as_usize),
+            offset: value.get("offset").and_then(json::Value::as_usize),
+            column: value.get("column").and_then(json::Value::as_usize).unwrap_or(0),
+            indent: 0,
+            text: field("text")?.as_str().ok_or_else(|| invalid("\"text\" is not a string"))?.to_string(),
+            ranges: vec![],
+            path_ranges: vec![],
+            captures: vec![],
+            invalid_regex: false,
+        });
+    }
+    Ok(records)
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
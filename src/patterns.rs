@@ -0,0 +1,26 @@
+// Built-in regexes for --named. Searches are case-insensitive, so the
+// character classes only spell out one case.
+pub const NAMED_PATTERNS: &[(&str, &str)] = &[
+    ("email", r"[a-z0-9._%+-]+@[a-z0-9-]+(\.[a-z0-9-]+)*\.[a-z]{2,}"),
+    ("url", r#"[a-z][a-z0-9+.-]*://[^\s<>()\[\]{}'"]+"#),
+    ("ipv4", r"\b((25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\.){3}(25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\b"),
+    ("uuid", r"\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b"),
+];
+
+pub fn named(name: &str) -> Result<String, String> {
+    NAMED_PATTERNS
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, pattern)| pattern.to_string())
+        .ok_or_else(|| {
+            let names: Vec<&str> = NAMED_PATTERNS.iter().map(|(key, _)| *key).collect();
+            format!("unknown named pattern: {} (expected one of {})", name, names.join(", "))
+        })
+}
+
+// The regex stored in a --pattern-file. Only the trailing newline is
+// dropped, since leading and inner whitespace may be part of the pattern.
+pub fn from_file(path: &str) -> Result<String, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("cannot read pattern file {}: {}", path, e))?;
+    Ok(content.trim_end_matches(['\n', '\r']).to_string())
+}
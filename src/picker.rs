@@ -0,0 +1,107 @@
+use crate::clip;
+use crate::preview::Area;
+use crossterm::{
+    cursor::MoveTo,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    ExecutableCommand,
+};
+use std::io::{self, Stdout};
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+// The directory browser opened with Ctrl-O to pick a new search root. It
+// lists `dir` itself (as ".") and then its subdirectories.
+pub struct Picker {
+    pub dir: PathBuf,
+    entries: Vec<String>,
+    pub selected: usize,
+}
+
+impl Picker {
+    pub fn open(dir: PathBuf) -> Picker {
+        // Hidden directories are left out as they are from the file walk
+        let mut entries: Vec<String> = WalkDir::new(&dir)
+            .min_depth(1)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        entries.sort_unstable();
+        entries.insert(0, ".".to_string());
+        Picker { dir, entries, selected: 0 }
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1).min(self.entries.len() - 1);
+    }
+
+    // The directory Enter would pick
+    pub fn chosen(&self) -> PathBuf {
+        match self.selected {
+            0 => self.dir.clone(),
+            index => self.dir.join(&self.entries[index]),
+        }
+    }
+
+    // Open the selected subdirectory
+    pub fn descend(&mut self) {
+        if self.selected > 0 {
+            *self = Picker::open(self.chosen());
+        }
+    }
+
+    // Open the parent directory, with the one we came from selected
+    pub fn ascend(&mut self) {
+        let Some(parent) = self.dir.parent() else {
+            return;
+        };
+        let from = self.dir.file_name().map(|name| name.to_string_lossy().into_owned());
+        let mut picker = Picker::open(parent.to_path_buf());
+        if let Some(index) = picker.entries.iter().position(|entry| Some(entry) == from.as_ref()) {
+            picker.selected = index;
+        }
+        *self = picker;
+    }
+}
+
+// A header row with the directory being browsed and the keys (`help`),
+// then a page of entries that keeps the selection in view
+pub fn draw(stdout: &mut Stdout, area: &Area, picker: &Picker, help: &str) -> io::Result<()> {
+    for row in 0..area.height as u16 {
+        stdout
+            .execute(MoveTo(area.x, area.y + row))?
+            .execute(Print(" ".repeat(area.width)))?;
+    }
+    if area.height == 0 {
+        return Ok(());
+    }
+    let header = format!("{}  ({})", picker.dir.display(), help);
+    stdout
+        .execute(MoveTo(area.x, area.y))?
+        .execute(SetForegroundColor(Color::DarkYellow))?
+        .execute(Print(clip(&header, area.width)))?
+        .execute(ResetColor)?;
+
+    let rows = area.height - 1;
+    let first = (picker.selected + 1).saturating_sub(rows);
+    for (row, (index, entry)) in picker.entries.iter().enumerate().skip(first).take(rows).enumerate() {
+        stdout.execute(MoveTo(area.x, area.y + 1 + row as u16))?;
+        if index == picker.selected {
+            stdout.execute(SetAttribute(Attribute::Reverse))?;
+        }
+        let name = if index == 0 { entry.clone() } else { format!("{}/", entry) };
+        stdout
+            .execute(SetForegroundColor(Color::Blue))?
+            .execute(Print(clip(&name, area.width)))?
+            .execute(SetAttribute(Attribute::Reset))?
+            .execute(ResetColor)?;
+    }
+    Ok(())
+}
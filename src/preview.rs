@@ -0,0 +1,97 @@
+use crate::options::Options;
+use crate::{syntax, whitespace};
+use crossterm::{
+    cursor::MoveTo,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    ExecutableCommand,
+};
+use std::io::{self, Stdout};
+use std::path::Path;
+
+// Screen area the preview is drawn into
+pub struct Area {
+    pub x: u16,
+    pub y: u16,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Width of the line number gutter, including the marker and a space
+const GUTTER_WIDTH: usize = 7;
+const TAB: &str = "    ";
+const TAB_SHOWN: &str = "→   ";
+
+// Draw the lines of `content` around `line` (1-based), with a line number
+// gutter: `context` lines either side, or as many as fit when it is None.
+// The matched line is marked, drawn in the match color and, across the
+// pane, on the focus background (options.preview_focus); with
+// --syntax-highlight the other lines are colored by the file's language,
+// and --show-whitespace marks tabs and trailing whitespace.
+pub fn draw(
+    stdout: &mut Stdout,
+    area: &Area,
+    path: &str,
+    content: &str,
+    line: usize,
+    context: Option<usize>,
+    options: &Options,
+) -> io::Result<()> {
+    let ext = options.mapped_extension(Path::new(path).extension().and_then(|ext| ext.to_str()).unwrap_or(""));
+    let text_width = area.width.saturating_sub(GUTTER_WIDTH);
+    let before = context.map_or(area.height / 2, |context| context.min(area.height / 2));
+    let first = line.saturating_sub(before).max(1);
+    let shown = context.map_or(area.height, |context| line + 1 - first + context).min(area.height);
+
+    for (row, (index, text)) in content
+        .lines()
+        .enumerate()
+        .skip(first - 1)
+        .take(shown)
+        .enumerate()
+    {
+        let number = index + 1;
+        // Expansion maps each char on its own, so the expanded body is a
+        // prefix of the expanded line and the trailing whitespace follows it
+        let expand = |text: &str| match options.show_whitespace {
+            true => whitespace::visible(&text.replace('\t', TAB_SHOWN)),
+            false => text.replace('\t', TAB),
+        };
+        let body_len = if options.show_whitespace { expand(text.trim_end()).len() } else { usize::MAX };
+        let text = expand(text);
+        let text = &text[..text.floor_char_boundary(text_width.min(text.len()))];
+        let (text, trailing) = text.split_at(body_len.min(text.len()));
+        let focused = number == line;
+
+        stdout
+            .execute(MoveTo(area.x, area.y + row as u16))?
+            .execute(SetForegroundColor(if focused { Color::Yellow } else { Color::DarkGrey }))?
+            .execute(Print(format!("{}{:>5} ", if focused { '▶' } else { ' ' }, number)))?;
+
+        if focused {
+            stdout
+                .execute(SetBackgroundColor(options.preview_focus.unwrap_or(Color::Reset)))?
+                .execute(SetForegroundColor(Color::Magenta))?
+                .execute(SetAttribute(Attribute::Bold))?
+                .execute(Print(text))?
+                .execute(SetAttribute(Attribute::Reset))?;
+        } else if options.syntax_highlight {
+            for (color, segment) in syntax::highlight(text, &ext) {
+                stdout
+                    .execute(SetForegroundColor(color))?
+                    .execute(Print(segment))?;
+            }
+        } else {
+            stdout.execute(ResetColor)?.execute(Print(text))?;
+        }
+        whitespace::print(stdout, trailing, true)?;
+        // The rest of the row too, so a short line stands out as well as a long one
+        if focused && let Some(background) = options.preview_focus {
+            let used = text.chars().count() + trailing.chars().count();
+            stdout
+                .execute(SetBackgroundColor(background))?
+                .execute(Print(" ".repeat(text_width.saturating_sub(used))))?;
+        }
+        stdout.execute(ResetColor)?;
+    }
+    Ok(())
+}
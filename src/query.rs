@@ -0,0 +1,75 @@
+use crate::options::Options;
+use std::path::Path;
+
+// A query split into its inline filters and the remaining pattern, e.g.
+// "path:src/ ext:rs error" scopes the pattern "error" to .rs files under src/
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedQuery {
+    pub pattern: String,
+    pub paths: Vec<String>,
+    pub extensions: Vec<String>,
+}
+
+// Extensions searched for each `lang:` name
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("rust", &["rs"]),
+    ("python", &["py"]),
+    ("javascript", &["js"]),
+    ("typescript", &["ts"]),
+    ("c", &["c", "h"]),
+    ("cpp", &["cpp", "h"]),
+    ("go", &["go"]),
+    ("java", &["java"]),
+    ("ruby", &["rb"]),
+    ("php", &["php"]),
+    ("shell", &["sh", "bash"]),
+    ("sql", &["sql"]),
+    ("markdown", &["md"]),
+    ("html", &["html"]),
+    ("css", &["css"]),
+    ("json", &["json"]),
+    ("yaml", &["yaml", "yml"]),
+    ("toml", &["toml"]),
+];
+
+impl ParsedQuery {
+    pub fn parse(query: &str) -> ParsedQuery {
+        let mut parsed = ParsedQuery::default();
+        let mut pattern_tokens = Vec::new();
+        // Split on single spaces so runs of spaces inside the pattern survive
+        for token in query.split(' ') {
+            if let Some(path) = token.strip_prefix("path:").filter(|p| !p.is_empty()) {
+                parsed.paths.push(path.trim_start_matches("./").to_string());
+            } else if let Some(ext) = token.strip_prefix("ext:").filter(|e| !e.is_empty()) {
+                parsed.extensions.push(ext.trim_start_matches('.').to_lowercase());
+            } else if let Some(exts) = token
+                .strip_prefix("lang:")
+                .and_then(|lang| LANGUAGES.iter().find(|(name, _)| name.eq_ignore_ascii_case(lang)))
+                .map(|(_, exts)| exts)
+            {
+                parsed.extensions.extend(exts.iter().map(|ext| ext.to_string()));
+            } else {
+                pattern_tokens.push(token);
+            }
+        }
+        parsed.pattern = pattern_tokens.join(" ").trim().to_string();
+        parsed
+    }
+
+    pub fn has_filters(&self) -> bool {
+        !self.paths.is_empty() || !self.extensions.is_empty()
+    }
+
+    // Whether a file passes the inline filters: under any given path (if any)
+    // and with any given extension (if any). A --map-ext extension passes
+    // as the one it is searched as, and as itself.
+    pub fn accepts(&self, file: &str, options: &Options) -> bool {
+        let relative = file.trim_start_matches("./");
+        let path_ok = self.paths.is_empty() || self.paths.iter().any(|p| relative.starts_with(p.as_str()));
+        let ext_ok = self.extensions.is_empty()
+            || Path::new(file).extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+                self.extensions.contains(&options.mapped_extension(ext)) || self.extensions.contains(&ext.to_lowercase())
+            });
+        path_ok && ext_ok
+    }
+}
@@ -0,0 +1,35 @@
+use crate::search::SearchResult;
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+// How strongly recent modification boosts a file. A file touched just now
+// scores (1 + weight) times its match count, one touched a day ago
+// (1 + weight / 2) times, and old files converge to their plain match count.
+pub const DEFAULT_RECENCY_WEIGHT: f64 = 4.0;
+
+// Reorder results so files with many matches and recent modification times
+// come first. Files with equal scores keep their walk order, and a file's
+// own lines keep their relative order.
+pub fn sort_by_recency(results: &mut [SearchResult], weight: f64) {
+    let now = SystemTime::now();
+    let mut match_counts: HashMap<&str, usize> = HashMap::new();
+    for result in results.iter() {
+        *match_counts.entry(result.path.as_str()).or_default() += 1;
+    }
+
+    let scores: HashMap<String, f64> = match_counts
+        .into_iter()
+        .map(|(file, count)| {
+            let age_days = fs::metadata(file)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .map(|age| age.as_secs_f64() / 86_400.0);
+            let recency = age_days.map_or(0.0, |days| 1.0 / (1.0 + days));
+            (file.to_string(), count as f64 * (1.0 + weight * recency))
+        })
+        .collect();
+
+    results.sort_by(|a, b| scores[&b.path].total_cmp(&scores[&a.path]));
+}
@@ -0,0 +1,968 @@
+use crate::options::{Case, Options, SortOrder};
+use crate::query::ParsedQuery;
+use crate::syntax::{self, Region};
+use crate::tree::{self, Tree};
+use crate::{archive, fields, fuzzy, git, glob, is_text_file, loose, natural, nfc, rank, text_extensions, whitespace};
+use lru::LruCache;
+use regex::{Captures, Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Decode bytes as Latin-1, so every byte becomes one char and nothing fails
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+// Make control characters printable without changing byte lengths: C0
+// controls (1 byte) become '.', C1 controls (2 bytes) become '·'
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\t' => c,
+            '\u{0}'..='\u{1f}' | '\u{7f}' => '.',
+            '\u{80}'..='\u{9f}' => '·',
+            _ => c,
+        })
+        .collect()
+}
+
+fn search_file_paths(files: &[String], query: &str, options: &Options) -> Vec<SearchResult> {
+    let query = if options.normalize { nfc::compose(query).text } else { query.to_string() };
+    if options.fuzzy {
+        return search_fuzzy_paths(files, &query, options);
+    }
+    let pattern = if options.glob {
+        match glob::to_regex(&query) {
+            Some(pattern) => pattern,
+            None => return vec![SearchResult::invalid_regex()],
+        }
+    } else if options.literal {
+        regex::escape(&query)
+    } else {
+        query.clone()
+    };
+    let re = match RegexBuilder::new(&pattern)
+        .case_insensitive(options.path_case.ignores_case(&query))
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(_) => {
+            return vec![SearchResult::invalid_regex()];
+        }
+    };
+    let Some(excludes) = build_excludes(options) else {
+        return vec![SearchResult::invalid_regex()];
+    };
+
+    // Globs are written relative to the search root, so drop the walk's "./"
+    files
+        .iter()
+        .filter(|f| {
+            let path = f.strip_prefix("./").unwrap_or(f);
+            if options.normalize { re.is_match(&nfc::compose(path).text) } else { re.is_match(path) }
+        })
+        .filter(|f| !excludes.iter().any(|exclude| exclude.is_match(f)))
+        .map(|f| SearchResult::file(f))
+        .collect()
+}
+
+// --fuzzy: paths holding the query's characters in order, best first;
+// equal scores keep walk order. With spaces in the query each word
+// matches a path component, and the components are highlighted.
+fn search_fuzzy_paths(files: &[String], query: &str, options: &Options) -> Vec<SearchResult> {
+    let Some(excludes) = build_excludes(options) else {
+        return vec![SearchResult::invalid_regex()];
+    };
+    let ignore_case = options.path_case.ignores_case(query);
+    let by_component = query.contains(char::is_whitespace);
+    let mut scored: Vec<(i64, SearchResult)> = files
+        .iter()
+        .filter(|f| !excludes.iter().any(|exclude| exclude.is_match(f)))
+        .filter_map(|f| {
+            let path = f.strip_prefix("./").unwrap_or(f);
+            // --normalize scores the composed path, whose offsets aren't the path's
+            let composed;
+            let scored_path = if options.normalize {
+                composed = nfc::compose(path).text;
+                composed.as_str()
+            } else {
+                path
+            };
+            let mut result = SearchResult::file(f);
+            let score = if by_component {
+                let (score, ranges) = fuzzy::score_components(query, scored_path, ignore_case)?;
+                if !options.normalize {
+                    let shift = f.len() - path.len();
+                    result.path_ranges = ranges.into_iter().map(|(start, end)| (start + shift, end + shift)).collect();
+                }
+                score
+            } else {
+                fuzzy::score(query, scored_path, ignore_case)?
+            };
+            Some((score, result))
+        })
+        .collect();
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, result)| result).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub path: String,
+    // 1-based line number of the match, 0 for rows that only name a file.
+    // With --record-separator, the number of the record.
+    pub line: usize,
+    // With --record-separator, the 1-based line of the file the record
+    // starts on, for the preview and --exec to go to
+    pub start_line: Option<usize>,
+    // Byte offset of the first match, reported for files searched with --binary
+    pub offset: Option<usize>,
+    // 1-based column of the first match in the line, in chars or (with
+    // --byte-offsets) bytes; 0 for rows that only name a file
+    pub column: usize,
+    // Leading whitespace dropped from `text` by --trim, in bytes
+    pub indent: usize,
+    pub text: String,
+    pub ranges: Vec<MatchRange>,
+    // Byte ranges of `path` to highlight: the components a --fuzzy query's
+    // words matched
+    pub path_ranges: Vec<(usize, usize)>,
+    // With --show-captures, each named group of the line's first match and
+    // the text it captured, in pattern order
+    pub captures: Vec<(String, String)>,
+    // Stands in for the results of a query that isn't a valid regex
+    pub invalid_regex: bool,
+}
+
+// Byte range of a match in `SearchResult::text`, and the index of the
+// pattern that produced it (0 for the typed query, then each --pattern)
+pub type MatchRange = (usize, usize, usize);
+
+impl SearchResult {
+    pub fn file(path: &str) -> SearchResult {
+        SearchResult {
+            path: path.to_string(),
+            line: 0,
+            start_line: None,
+            offset: None,
+            column: 0,
+            indent: 0,
+            text: "".to_string(),
+            ranges: vec![],
+            path_ranges: vec![],
+            captures: vec![],
+            invalid_regex: false,
+        }
+    }
+
+    pub fn invalid_regex() -> SearchResult {
+        SearchResult { invalid_regex: true, ..SearchResult::file("") }
+    }
+
+    pub fn is_invalid_regex(&self) -> bool {
+        self.invalid_regex
+    }
+
+    // The line of the file to show or open the result at
+    pub fn file_line(&self) -> usize {
+        self.start_line.unwrap_or(self.line)
+    }
+}
+
+// Facts about a search beyond its results
+#[derive(Debug, Default)]
+pub struct SearchStats {
+    // The --timeout-ms deadline passed, or the search was interrupted,
+    // before every file was searched
+    pub partial: bool,
+    // How much was read for --stats: files opened, lines (or records)
+    // examined and their bytes
+    pub files: usize,
+    pub lines: usize,
+    pub bytes: usize,
+    // Files given up on after --file-timeout-ms
+    pub slow_files: usize,
+}
+
+impl SearchStats {
+    fn add(&mut self, other: &SearchStats) {
+        self.partial |= other.partial;
+        self.files += other.files;
+        self.lines += other.lines;
+        self.bytes += other.bytes;
+        self.slow_files += other.slow_files;
+    }
+}
+
+// What the interactive result list shows. The empty query lists every file,
+// which on a big tree is left as the file list itself and only turned into
+// rows for the window being drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rows {
+    Results(Vec<SearchResult>),
+    // The first N collected files, in walk order
+    Listing(usize),
+    // --tree: the results grouped under their directories and files
+    Tree(Tree),
+}
+
+impl Rows {
+    pub fn len(&self) -> usize {
+        match self {
+            Rows::Results(results) => results.len(),
+            Rows::Listing(count) => *count,
+            Rows::Tree(tree) => tree.rows.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get<'a>(&'a self, index: usize, files: &[String]) -> Option<Cow<'a, SearchResult>> {
+        match self {
+            Rows::Results(results) => results.get(index).map(Cow::Borrowed),
+            Rows::Listing(count) if index < *count => files.get(index).map(|f| Cow::Owned(SearchResult::file(f))),
+            Rows::Listing(_) => None,
+            Rows::Tree(tree) => tree.rows.get(index).map(|row| match &row.kind {
+                tree::Kind::Node { header, .. } => Cow::Borrowed(header.as_ref()),
+                tree::Kind::Match(result) => Cow::Borrowed(&tree.results[*result]),
+            }),
+        }
+    }
+
+    pub fn same_matches(&self, other: &Rows) -> bool {
+        match (self, other) {
+            (Rows::Results(a), Rows::Results(b)) => same_matches(a, b),
+            (Rows::Listing(a), Rows::Listing(b)) => a == b,
+            (Rows::Tree(a), Rows::Tree(b)) => same_matches(&a.results, &b.results),
+            _ => false,
+        }
+    }
+}
+
+// Whether the query lists every file in walk order, so the list can be
+// drawn straight from the files (other sort orders rank the whole listing)
+pub fn lists_all_files(query: &str, options: &Options) -> bool {
+    query.is_empty() && options.patterns.is_empty() && options.sort == SortOrder::Walk
+}
+
+pub fn search_file_contents(
+    files: &[String],
+    query: &str,
+    content_cache: &mut LruCache<String, String>,
+    terminal_width: usize,
+    options: &Options,
+    stats: &mut SearchStats,
+    interrupt: &(dyn Fn() -> bool + Sync),
+) -> Vec<SearchResult> {
+    let deadline = options.timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+
+    // Inline path:/ext:/lang: tokens narrow the files, the rest is the pattern
+    let parsed = ParsedQuery::parse(query);
+    let scoped: Vec<String>;
+    let files = if parsed.has_filters() {
+        scoped = files.iter().filter(|f| parsed.accepts(f, options)).cloned().collect();
+        &scoped
+    } else {
+        files
+    };
+    let query = parsed.pattern.as_str();
+
+    if query.is_empty() && options.patterns.is_empty() {
+        let mut listing = files
+            .iter()
+            .map(|f| SearchResult::file(f))
+            .collect::<Vec<SearchResult>>();
+        sort_results(&mut listing, options);
+        return listing;
+    }
+
+    if options.paths {
+        return search_file_paths(files, query, options);
+    }
+
+    let (Some(regexes), Some(excludes)) = (build_regexes(query, options), build_excludes(options)) else {
+        return vec![SearchResult::invalid_regex()];
+    };
+
+    let extensions = text_extensions(options);
+    let threads = options
+        .threads
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    // Each worker takes a contiguous run of files, so concatenating the
+    // workers' output in order keeps the walk order
+    let chunk_size = files.len().div_ceil(threads).max(1);
+    let cache = Mutex::new(content_cache);
+    let search_chunk = |chunk: &[String]| {
+        let mut found = Vec::new();
+        let mut stats = SearchStats::default();
+        for file in chunk {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) || interrupt() {
+                stats.partial = true;
+                return (found, stats);
+            }
+
+            // With --binary, files outside the text extensions are read as Latin-1
+            let binary = options.binary && !is_text_file(Path::new(file), &extensions);
+
+            let Some(content) = load_shared(file, binary, &cache, options) else {
+                continue;
+            };
+            stats.files += 1;
+            stats.bytes += content.len();
+            match search_lines(file, &content, binary, &regexes, terminal_width, options, &mut found) {
+                Some(lines) => stats.lines += lines,
+                None => stats.slow_files += 1,
+            }
+        }
+        (found, stats)
+    };
+    let chunks: Vec<(Vec<(String, SearchResult)>, SearchStats)> = if threads == 1 {
+        vec![search_chunk(files)]
+    } else {
+        thread::scope(|scope| {
+            let workers: Vec<_> = files
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| search_chunk(chunk)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("search worker panicked"))
+                .collect()
+        })
+    };
+
+    let mut matches = Vec::new();
+    let mut seen_lines = HashSet::new();
+    for (found, chunk_stats) in chunks {
+        stats.add(&chunk_stats);
+        for (line, result) in found {
+            if excludes.iter().any(|exclude| exclude.is_match(&line)) {
+                continue;
+            }
+            // With --unique, only the first occurrence of a line's text is kept
+            if options.unique && !seen_lines.insert(line) {
+                continue;
+            }
+            matches.push(result);
+        }
+    }
+
+    // Each file's matches are adjacent; keep them in ascending line order
+    // whatever order they were found in. The sort is stable, so the order
+    // of files (and of --record-separator records on one line) holds.
+    for file_matches in matches.chunk_by_mut(|a, b| a.path == b.path) {
+        file_matches.sort_by_key(|result| result.line);
+    }
+    sort_results(&mut matches, options);
+    matches
+}
+
+// How file contents (and --not patterns) are matched; --path-case only
+// applies to the path modes
+pub const CONTENT_CASE: Case = Case::Insensitive;
+
+// The typed query is pattern 0 and each --pattern follows it, so a
+// pattern keeps its highlight color whether or not a query is typed.
+// None if any of them is not a valid regex.
+fn build_regexes(query: &str, options: &Options) -> Option<Vec<(usize, Regex)>> {
+    let mut patterns = vec![query];
+    patterns.extend(options.patterns.iter().map(String::as_str));
+    let mut regexes = Vec::new();
+    for (index, pattern) in patterns.into_iter().enumerate() {
+        // --loose patterns are words, normalized like the lines they match
+        let pattern = if options.loose {
+            regex::escape(loose::normalize(pattern).text.trim())
+        } else if options.normalize {
+            nfc::compose(pattern).text
+        } else {
+            pattern.to_string()
+        };
+        // --literal: the text itself, metacharacters and all
+        let pattern = if options.literal && !options.loose { regex::escape(&pattern) } else { pattern };
+        if pattern.is_empty() {
+            continue;
+        }
+        // --line-regexp: the pattern has to match the whole line, like grep -x
+        let pattern = if options.line_regexp { format!("^(?:{})$", pattern) } else { pattern };
+        let ignore_case = CONTENT_CASE.ignores_case(&pattern);
+        regexes.push((index, RegexBuilder::new(&pattern).case_insensitive(ignore_case).build().ok()?));
+    }
+    Some(regexes)
+}
+
+// --not patterns: lines (or paths) matching any of them are dropped from
+// the results, whatever else they match
+fn build_excludes(options: &Options) -> Option<Vec<Regex>> {
+    options
+        .excludes
+        .iter()
+        .map(|pattern| RegexBuilder::new(pattern).case_insensitive(CONTENT_CASE.ignores_case(pattern)).build().ok())
+        .collect()
+}
+
+// Search the `text` of records read with --from-json instead of file
+// contents. Matching records keep their path, line and offset; the ranges
+// are those of this search.
+pub fn search_records(
+    records: &[SearchResult],
+    query: &str,
+    terminal_width: usize,
+    options: &Options,
+) -> Vec<SearchResult> {
+    let parsed = ParsedQuery::parse(query);
+    let records = records.iter().filter(|record| parsed.accepts(&record.path, options));
+    let query = parsed.pattern.as_str();
+
+    if query.is_empty() && options.patterns.is_empty() {
+        return records.cloned().collect();
+    }
+    let (Some(regexes), Some(excludes)) = (build_regexes(query, options), build_excludes(options)) else {
+        return vec![SearchResult::invalid_regex()];
+    };
+
+    let mut matches = Vec::new();
+    let mut seen_lines = HashSet::new();
+    for record in records {
+        let mut found = Vec::new();
+        search_lines(&record.path, &record.text, false, &regexes, terminal_width, options, &mut found);
+        for (line, mut result) in found {
+            if excludes.iter().any(|exclude| exclude.is_match(&line)) {
+                continue;
+            }
+            if options.unique && !seen_lines.insert(line) {
+                continue;
+            }
+            result.line = record.line;
+            result.start_line = record.start_line;
+            result.offset = record.offset;
+            result.column = record.column;
+            matches.push(result);
+        }
+    }
+    sort_by_severity(&mut matches, options);
+    matches
+}
+
+// --file-timeout-ms is checked before each line and after every this many
+// matches within one
+const DEADLINE_CHECK_MATCHES: usize = 1024;
+
+// The (start, end) of each match of `re` in `text`, or None if the
+// deadline passes first. A huge line can hold millions of matches; a single
+// scan that finds nothing can't be stopped.
+fn find_until(re: &Regex, text: &str, deadline: Option<Instant>) -> Option<Vec<(usize, usize)>> {
+    let mut ranges = vec![];
+    for (count, mat) in re.find_iter(text).enumerate() {
+        if count % DEADLINE_CHECK_MATCHES == DEADLINE_CHECK_MATCHES - 1
+            && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return None;
+        }
+        ranges.push((mat.start(), mat.end()));
+    }
+    Some(ranges)
+}
+
+// Push a result for every line of `content` that any regex matches, paired
+// with the full line so --unique can compare lines before truncation. With a
+// --record-separator, "lines" are the records between separators. Returns
+// how many lines were examined, or None if --file-timeout-ms ran out first,
+// in which case the file's results are dropped.
+fn search_lines(
+    file: &str,
+    content: &str,
+    binary: bool,
+    regexes: &[(usize, Regex)],
+    terminal_width: usize,
+    options: &Options,
+    found: &mut Vec<(String, SearchResult)>,
+) -> Option<usize> {
+    let deadline = options.file_timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+    let found_before = found.len();
+    // \r\n line endings count as \n, so patterns that run into or across
+    // line ends match CRLF files the same as LF ones. Binary offsets are
+    // into the file as is, so binary content keeps them.
+    let normalized;
+    let content = if !binary && !options.keep_cr && content.contains('\r') {
+        normalized = content.replace("\r\n", "\n");
+        normalized.as_str()
+    } else {
+        content
+    };
+    let separator = options.record_separator.as_deref();
+    let window = options.window.filter(|&lines| lines > 1);
+    let lines: Box<dyn Iterator<Item = &str>> = match separator {
+        // Newlines next to a separator (extra blank lines, a final newline)
+        // are not part of the record
+        Some(separator) => Box::new(content.split(separator).map(|record| record.trim_matches(['\n', '\r']))),
+        None if let Some(window) = window => Box::new(windows(content, window, options.keep_cr)),
+        None if options.keep_cr => Box::new(content.split_terminator('\n')),
+        // A last line without a newline can still end in \r
+        None => Box::new(content.lines().map(|line| line.strip_suffix('\r').unwrap_or(line))),
+    };
+    // Records and windows can span lines; spaces keep them on one row at
+    // the same length
+    let joined = separator.is_some() || window.is_some();
+    // --max-columns caps the text whatever the terminal width
+    let max_columns = options.max_columns.unwrap_or(usize::MAX);
+    // --in-comments/--in-strings: the comments and strings of the file, if
+    // its language is known; other files match anywhere
+    let regions = if (options.in_comments || options.in_strings) && !binary {
+        let ext = Path::new(file).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        syntax::regions(content, &options.mapped_extension(ext))
+    } else {
+        None
+    };
+    // With --git-diff, only lines changed since HEAD can match
+    let changed = options
+        .changed_lines
+        .as_ref()
+        .map(|changed| changed.get(file.strip_prefix("./").unwrap_or(file)));
+    // --range: lines outside it are passed over unmatched. A range past the
+    // end of the file finds nothing; an end past it stops at the last line.
+    let (first, last) = options.range.unwrap_or((1, usize::MAX));
+    let mut examined = 0;
+    // With --record-separator: newlines in content[..counted]
+    let (mut newlines, mut counted) = (0, 0);
+    for (line_index, line) in lines.enumerate().skip(first - 1).take(last - (first - 1)) {
+        examined += 1;
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            found.truncate(found_before);
+            return None;
+        }
+        if let Some(ranges) = changed
+            && !ranges.is_some_and(|ranges| ranges.iter().any(|range| range.contains(&(line_index + 1))))
+        {
+            continue;
+        }
+        // A substring search is much cheaper than most regexes
+        if let Some(literal) = &options.prefilter
+            && !line.contains(literal.as_str())
+        {
+            continue;
+        }
+        // --field: only the field is matched, so anchors and the rest of
+        // the line play no part; ranges are moved back onto the line
+        let (field_start, field) = match options.field {
+            Some(n) => match fields::span(line, n, options.delimiter.unwrap_or(',')) {
+                Some((start, end)) => (start, &line[start..end]),
+                None => continue,
+            },
+            None => (0, line),
+        };
+        let ranges = || -> Option<Vec<(usize, usize, usize)>> {
+            let mut match_ranges = vec![];
+            if options.loose {
+                // Match the normalized line, then map back to the original bytes
+                let normalized = loose::normalize(field);
+                for (index, re) in regexes {
+                    for (start, end) in find_until(re, &normalized.text, deadline)?.into_iter().filter(|(start, end)| start < end) {
+                        let (start, end) = normalized.original(start, end);
+                        match_ranges.push((start, end, *index));
+                    }
+                }
+            } else if options.normalize {
+                let composed = nfc::compose(field);
+                for (index, re) in regexes {
+                    for (start, end) in find_until(re, &composed.text, deadline)?.into_iter().filter(|(start, end)| start < end) {
+                        let (start, end) = composed.original(start, end);
+                        match_ranges.push((start, end, *index));
+                    }
+                }
+            } else {
+                for (index, re) in regexes {
+                    match_ranges.extend(find_until(re, field, deadline)?.into_iter().map(|(start, end)| (start, end, *index)));
+                }
+            }
+            Some(match_ranges)
+        };
+        let Some(mut match_ranges) = ranges() else {
+            found.truncate(found_before);
+            return None;
+        };
+        if field_start > 0 {
+            for (start, end, _) in &mut match_ranges {
+                *start += field_start;
+                *end += field_start;
+            }
+        }
+        if let Some(regions) = &regions {
+            let line_start = line.as_ptr() as usize - content.as_ptr() as usize;
+            match_ranges.retain(|&(start, _, _)| in_region(regions, line_start + start, options));
+        }
+        // A window reports the matches that start on its first line; the
+        // others belong to the windows of the lines they start on
+        if window.is_some() {
+            let first_line = line.find('\n').unwrap_or(line.len());
+            match_ranges.retain(|&(start, _, _)| start <= first_line);
+        }
+        match_ranges.sort_unstable();
+        let first_match_start = match_ranges.first().map(|&(start, _, _)| start);
+        if match_ranges.is_empty() {
+            continue;
+        }
+        // The line a record starts on, counting newlines on from the last
+        // record that matched
+        let start_line = separator.map(|_| {
+            let at = line.as_ptr() as usize - content.as_ptr() as usize;
+            newlines += content[counted..at].matches('\n').count();
+            counted = at;
+            newlines + 1
+        });
+
+        // --only-matching: a row for each match showing just the matched
+        // text, which is also what --unique and --not compare
+        if options.only_matching {
+            let max_text_len = terminal_width.saturating_sub(33).min(max_columns).max(4);
+            let line_start = line.as_ptr() as usize - content.as_ptr() as usize;
+            for &(start, end, pattern) in &match_ranges {
+                let matched = &line[start..end];
+                let mut text = if binary { printable(matched) } else { matched.to_string() };
+                if joined {
+                    text = text.replace(['\n', '\r'], " ");
+                }
+                let mut shown = text.len();
+                if shown > max_text_len {
+                    shown = text.floor_char_boundary(max_text_len - 3);
+                    text = format!("{}...", &text[..shown]);
+                }
+                // Captures of this match in particular; --loose and
+                // --normalize match a rebuilt line, so its offsets don't
+                // apply to `line`
+                let captures = match regexes.iter().find(|(index, _)| *index == pattern) {
+                    Some((_, re)) if options.show_captures && !options.loose && !options.normalize => re
+                        .captures_at(field, start - field_start)
+                        .map(|caps| capture_values(re, &caps))
+                        .unwrap_or_default(),
+                    _ => vec![],
+                };
+                found.push((
+                    matched.to_string(),
+                    SearchResult {
+                        path: file.to_string(),
+                        line: line_index + 1,
+                        start_line,
+                        indent: 0,
+                        offset: binary.then(|| content[..line_start + start].chars().count()),
+                        column: column(line, start, options),
+                        text,
+                        ranges: vec![(0, shown, pattern)],
+                        path_ranges: vec![],
+                        captures,
+                        invalid_regex: false,
+                    },
+                ));
+            }
+            continue;
+        }
+
+        // With --trim, the shown text starts after the leading whitespace
+        let indent = if options.trim { line.len() - line.trim_start().len() } else { 0 };
+        // --collapse-whitespace shows runs of spaces and tabs as one space,
+        // with the matches moved to suit; what matched is unchanged
+        let collapsed;
+        let (shown, match_ranges) = if options.collapse_whitespace {
+            collapsed = whitespace::collapse(line);
+            let ranges = match_ranges
+                .into_iter()
+                .map(|(start, end, pattern)| {
+                    let (start, end) = collapsed.range(start, end);
+                    (start, end, pattern)
+                })
+                .collect();
+            (collapsed.text.as_str(), ranges)
+        } else {
+            (line, match_ranges)
+        };
+        let shown_indent = if options.trim { shown.len() - shown.trim_start().len() } else { 0 };
+        let first_shown_start = match_ranges.first().map(|&(start, _, _)| start);
+
+        // Initialize truncation variables
+        let max_text_len = terminal_width.saturating_sub(33).min(max_columns); // 30 for path + 3 for padding
+        let start_pos;
+        let prefix_offset;
+        let matched_line = if shown.len() - shown_indent > max_text_len {
+            let start = first_shown_start.unwrap_or(0);
+            // Up to 20 chars before the match, less in a narrow cap so
+            // the match itself still fits
+            let context = 20.min(max_text_len / 4).min(start);
+            // Cut on char boundaries so multi-byte text can't split
+            start_pos = shown.floor_char_boundary(start.saturating_sub(context)).max(shown_indent);
+            let end_pos = shown.floor_char_boundary((start_pos + max_text_len).min(shown.len()));
+            let mut truncated = shown[start_pos..end_pos].to_string();
+            prefix_offset = if start_pos > shown_indent {
+                truncated = format!("...{}", truncated);
+                3 // Account for "..."
+            } else {
+                0
+            };
+            if end_pos < shown.len() {
+                truncated.push_str("...");
+            }
+            truncated
+        } else {
+            start_pos = shown_indent;
+            prefix_offset = 0;
+            shown[shown_indent..].to_string()
+        };
+
+        // Adjust match ranges for truncated line; a match cut by the start
+        // (or the trimmed indent) keeps its visible part
+        let adjusted_ranges = match_ranges
+            .into_iter()
+            .filter(|&(_, end, _)| end > start_pos)
+            .map(|(start, end, pattern)| {
+                let new_start = start.max(start_pos) - start_pos + prefix_offset;
+                let new_end = end - start_pos + prefix_offset;
+                (new_start, new_end.min(matched_line.len()), pattern)
+            })
+            .filter(|&(start, end, _)| start < matched_line.len() && end <= matched_line.len())
+            .collect::<Vec<MatchRange>>();
+
+        // Latin-1 maps each byte to one char, so a binary match's
+        // byte offset in the file is its char offset in the content
+        let offset = binary.then(|| {
+            let line_start = line.as_ptr() as usize - content.as_ptr() as usize;
+            content[..line_start + first_match_start.unwrap_or(0)].chars().count()
+        });
+        let matched_line = if binary { printable(&matched_line) } else { matched_line };
+        let matched_line = if joined {
+            matched_line.replace(['\n', '\r'], " ")
+        } else {
+            matched_line
+        };
+
+        found.push((
+            line.to_string(),
+            SearchResult {
+                path: file.to_string(),
+                line: line_index + 1,
+                start_line,
+                indent,
+                offset,
+                column: first_match_start.map_or(0, |start| column(line, start, options)),
+                text: matched_line,
+                ranges: adjusted_ranges,
+                path_ranges: vec![],
+                captures: if options.show_captures { named_captures(field, regexes) } else { vec![] },
+                invalid_regex: false,
+            },
+        ));
+    }
+    Some(examined)
+}
+
+// The 1-based column of byte `at` of `line`, where a match starts: counted
+// in chars, like an editor's cursor, or with --byte-offsets in bytes, like
+// the offsets regex and byte-oriented tools report. A row without a match
+// has column 0.
+fn column(line: &str, at: usize, options: &Options) -> usize {
+    if options.byte_offsets {
+        at + 1
+    } else {
+        line[..at].chars().count() + 1
+    }
+}
+
+// Whether byte `at` of the file falls in a comment (--in-comments) or a
+// string (--in-strings)
+fn in_region(regions: &[(usize, usize, Region)], at: usize, options: &Options) -> bool {
+    let index = regions.partition_point(|&(start, _, _)| start <= at);
+    index > 0
+        && match regions[index - 1] {
+            (_, end, region) if at < end => match region {
+                Region::Comment => options.in_comments,
+                Region::String => options.in_strings,
+            },
+            _ => false,
+        }
+}
+
+// --window N: for each line, the slice of `content` running from it to the
+// end of the N-1 lines after it (fewer at the end), line breaks included.
+// The last line's \r is cut off too unless `keep_cr`.
+fn windows(content: &str, size: usize, keep_cr: bool) -> impl Iterator<Item = &str> {
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(at, _)| at + 1))
+        .filter(|&start| start < content.len())
+        .collect();
+    // Where line `index` ends, before its line break
+    let end = move |starts: &[usize], index: usize| {
+        let line = match starts.get(index + 1) {
+            Some(&next) => &content[..next - 1],
+            None => content.strip_suffix('\n').unwrap_or(content),
+        };
+        line.len() - usize::from(!keep_cr && line.ends_with('\r'))
+    };
+    (0..starts.len()).map(move |index| {
+        let last = (index + size - 1).min(starts.len() - 1);
+        &content[starts[index]..end(&starts, last)]
+    })
+}
+
+// Named groups of each pattern's first match in `line`. Groups that took
+// no part in the match are left out.
+fn named_captures(line: &str, regexes: &[(usize, Regex)]) -> Vec<(String, String)> {
+    let mut captures = Vec::new();
+    for (_, re) in regexes {
+        if let Some(caps) = re.captures(line) {
+            captures.extend(capture_values(re, &caps));
+        }
+    }
+    captures
+}
+
+fn capture_values(re: &Regex, caps: &Captures) -> Vec<(String, String)> {
+    re.capture_names()
+        .flatten()
+        .filter_map(|name| Some((name.to_string(), caps.name(name)?.as_str().to_string())))
+        .collect()
+}
+
+// File contents through the cache. Revision contents are cached under the
+// same `rev:path` spec git uses so they never collide with working tree files.
+pub fn load_content(
+    file: &str,
+    binary: bool,
+    content_cache: &mut LruCache<String, String>,
+    options: &Options,
+) -> Option<String> {
+    load_shared(file, binary, &Mutex::new(content_cache), options)
+}
+
+// load_content for a cache shared between search workers
+fn load_shared(
+    file: &str,
+    binary: bool,
+    content_cache: &Mutex<&mut LruCache<String, String>>,
+    options: &Options,
+) -> Option<String> {
+    let cache_key = match &options.rev {
+        Some(rev) => format!("{}:{}", rev, file),
+        None => file.to_string(),
+    };
+    // The lock is only held around cache lookups, never while reading
+    if let Some(content) = content_cache.lock().unwrap().get(&cache_key) {
+        return Some(content.clone());
+    }
+    let read = match &options.rev {
+        Some(rev) => git::read_file(rev, file),
+        None if let Some((archive, entry)) = archive::split(file) => archive::read_entry(Path::new(archive), entry)
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))),
+        None if binary => fs::read(file).map(|bytes| decode_latin1(&bytes)),
+        None => fs::read_to_string(file),
+    };
+    let content = read.ok()?;
+    content_cache.lock().unwrap().put(cache_key, content.clone());
+    Some(content)
+}
+
+// Whether two result lists hold the same matches in the same order,
+// ignoring the displayed text and highlight ranges
+pub fn same_matches(a: &[SearchResult], b: &[SearchResult]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| x.path == y.path && x.line == y.line && x.is_invalid_regex() == y.is_invalid_regex())
+}
+
+fn sort_results(results: &mut [SearchResult], options: &Options) {
+    match options.sort {
+        SortOrder::Walk => {}
+        SortOrder::Recency => {
+            rank::sort_by_recency(results, options.recency_weight.unwrap_or(rank::DEFAULT_RECENCY_WEIGHT))
+        }
+        // Stable, so each file's lines keep their order
+        SortOrder::Path if options.natural_sort => results.sort_by(|a, b| natural::compare(&a.path, &b.path)),
+        SortOrder::Path => results.sort_by(|a, b| a.path.cmp(&b.path)),
+    }
+    sort_by_severity(results, options);
+}
+
+// Lines matching a heavier annotation pattern (FIXME over TODO) come
+// first. The sort is stable, so equal lines keep the order above.
+fn sort_by_severity(results: &mut [SearchResult], options: &Options) {
+    if options.severities.iter().all(Option::is_none) {
+        return;
+    }
+    let weight = |result: &SearchResult| {
+        result
+            .ranges
+            .iter()
+            .filter_map(|&(_, _, pattern)| options.severities.get(pattern.checked_sub(1)?)?.as_ref())
+            .map(|severity| severity.weight)
+            .max()
+            .unwrap_or(0)
+    };
+    results.sort_by_key(|result| std::cmp::Reverse(weight(result)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A file checked out with Windows line endings, its last line without one
+    const CRLF: &str = "alpha = 1\r\nbeta = 2\r\ngamma\r";
+
+    fn search(pattern: &str, options: &Options) -> Vec<SearchResult> {
+        let regexes = vec![(0, Regex::new(pattern).unwrap())];
+        let mut found = Vec::new();
+        search_lines("crlf.txt", CRLF, false, &regexes, 200, options, &mut found);
+        found.into_iter().map(|(_, result)| result).collect()
+    }
+
+    #[test]
+    fn crlf_stripped_by_default() {
+        let options = Options::default();
+        let results = search(r"\d$", &options);
+        assert_eq!(results.iter().map(|r| r.line).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(results[0].text, "alpha = 1");
+        assert_eq!(results[1].text, "beta = 2");
+        // The unterminated last line loses its \r too
+        let results = search("^gamma$", &options);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].text, "gamma");
+    }
+
+    #[test]
+    fn crlf_kept_with_keep_cr() {
+        let options = Options { keep_cr: true, ..Options::default() };
+        assert!(search(r"\d$", &options).is_empty());
+        let results = search(r"\d\r$", &options);
+        assert_eq!(results.iter().map(|r| r.line).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(results[0].text, "alpha = 1\r");
+        assert_eq!(search("^gamma\r$", &options).len(), 1);
+        // Across a --window the last line keeps its \r as well
+        let options = Options { window: Some(2), ..options };
+        let results = search(r"^beta = 2\r\ngamma\r$", &options);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line, 2);
+    }
+
+    #[test]
+    fn range_past_the_end() {
+        let regexes = vec![(0, Regex::new("a").unwrap())];
+        let lines = |range| {
+            let options = Options { range: Some(range), ..Options::default() };
+            let mut found = Vec::new();
+            search_lines("range.txt", "a1\na2\na3\n", false, &regexes, 200, &options, &mut found);
+            found.into_iter().map(|(_, result)| result.line).collect::<Vec<_>>()
+        };
+        // An end past the last line stops there
+        assert_eq!(lines((2, 100)), [2, 3]);
+        assert_eq!(lines((1, usize::MAX)), [1, 2, 3]);
+        // A start past it finds nothing
+        assert!(lines((4, 10)).is_empty());
+        assert!(lines((usize::MAX, usize::MAX)).is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::json;
+use crate::options::Options;
+use crate::output;
+use crate::search::{search_file_contents, SearchResult, SearchStats};
+use lru::LruCache;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+
+// Answer search requests on a Unix socket until killed, keeping the file
+// list and content cache warm between them. Each request is a JSON line:
+//   {"query": "PATTERN", "limit": N}   search, optionally keeping N results
+//   {"reload": true}                   re-collect the files and drop the cache
+// A search answers with --json lines and then
+// {"done":true,"count":N,"partial":BOOL}, a reload with
+// {"done":true,"files":N}, and a bad request with {"error":"..."}.
+// Clients are served one at a time, each for as many requests as it sends.
+pub fn run(
+    socket: &str,
+    mut files: Vec<String>,
+    content_cache: &mut LruCache<String, String>,
+    options: &Options,
+    reload: &dyn Fn() -> Result<Vec<String>, String>,
+) -> io::Result<()> {
+    // A socket left behind by an earlier server would make bind fail
+    if fs::symlink_metadata(socket).is_ok_and(|meta| meta.file_type().is_socket()) {
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    eprintln!("termiscope: serving {} files on {}", files.len(), socket);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("termiscope: {}", e);
+                continue;
+            }
+        };
+        // A client hanging up mid-answer only ends that client
+        if let Err(e) = serve_client(stream, &mut files, content_cache, options, reload) {
+            eprintln!("termiscope: client: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn serve_client(
+    stream: UnixStream,
+    files: &mut Vec<String>,
+    content_cache: &mut LruCache<String, String>,
+    options: &Options,
+    reload: &dyn Fn() -> Result<Vec<String>, String>,
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(stream.try_clone()?);
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match json::parse(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                write_error(&mut out, &format!("invalid request: {}", e))?;
+                continue;
+            }
+        };
+
+        if request.get("reload") == Some(&json::Value::Bool(true)) {
+            match reload() {
+                Ok(fresh) => {
+                    *files = fresh;
+                    content_cache.clear();
+                    writeln!(out, "{{\"done\":true,\"files\":{}}}", files.len())?;
+                }
+                Err(e) => write_error(&mut out, &e)?,
+            }
+            out.flush()?;
+            continue;
+        }
+
+        let Some(query) = request.get("query").and_then(json::Value::as_str) else {
+            write_error(&mut out, "request needs a \"query\" string or \"reload\": true")?;
+            continue;
+        };
+        let mut stats = SearchStats::default();
+        let mut results = search_file_contents(files, query, content_cache, usize::MAX, options, &mut stats, &|| false);
+        if results.first().is_some_and(SearchResult::is_invalid_regex) {
+            let kind = if options.glob { "glob" } else { "regex" };
+            write_error(&mut out, &format!("invalid {} pattern: {}", kind, query))?;
+            continue;
+        }
+        if let Some(limit) = request.get("limit").and_then(json::Value::as_usize) {
+            results.truncate(limit);
+        }
+        output::write_json(&mut out, &results)?;
+        writeln!(out, "{{\"done\":true,\"count\":{},\"partial\":{}}}", results.len(), stats.partial)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn write_error<W: Write>(out: &mut W, message: &str) -> io::Result<()> {
+    writeln!(out, "{{\"error\":{}}}", output::json_string(message))?;
+    out.flush()
+}
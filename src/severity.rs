@@ -0,0 +1,81 @@
+use crate::config::Config;
+use crossterm::style::Color;
+
+// Annotation words given as a --pattern (TODO, FIXME, ...) get a color of
+// their own and a weight: lines holding heavier words are listed first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Severity {
+    pub color: Color,
+    pub weight: u32,
+}
+
+const DEFAULTS: &[(&str, Color, u32)] = &[
+    ("FIXME", Color::Red, 3),
+    ("BUG", Color::Red, 3),
+    ("XXX", Color::Red, 3),
+    ("HACK", Color::Magenta, 2),
+    ("TODO", Color::Yellow, 1),
+    ("NOTE", Color::Cyan, 0),
+];
+
+// The severity of each --pattern, in order. Words are compared without
+// case.
+pub fn for_patterns(patterns: &[String], config: &Config) -> Result<Vec<Option<Severity>>, String> {
+    let table = table(config)?;
+    Ok(patterns
+        .iter()
+        .map(|pattern| {
+            table
+                .iter()
+                .find(|(word, _)| word.eq_ignore_ascii_case(pattern))
+                .map(|(_, severity)| severity.clone())
+        })
+        .collect())
+}
+
+// The default words, with [severity] entries in the config file
+// (`WORD = COLOR WEIGHT`) replacing or adding to them
+pub fn table(config: &Config) -> Result<Vec<(String, Severity)>, String> {
+    let mut table: Vec<(String, Severity)> = DEFAULTS
+        .iter()
+        .map(|&(word, color, weight)| (word.to_string(), Severity { color, weight }))
+        .collect();
+    for (word, spec) in &config.severities {
+        let severity = parse(spec).ok_or_else(|| {
+            format!("[severity] {}: expected `COLOR WEIGHT`, e.g. `red 3`, not `{}`", word, spec)
+        })?;
+        table.retain(|(known, _)| !known.eq_ignore_ascii_case(word));
+        table.push((word.clone(), severity));
+    }
+    Ok(table)
+}
+
+// "red 3", "dark-yellow 1"
+fn parse(spec: &str) -> Option<Severity> {
+    let mut parts = spec.split_whitespace();
+    let color = color(parts.next()?)?;
+    let weight = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some(Severity { color, weight })
+}
+
+pub fn color(name: &str) -> Option<Color> {
+    Some(match name.to_lowercase().replace(['-', '_'], "").as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "darkred" => Color::DarkRed,
+        "green" => Color::Green,
+        "darkgreen" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "darkyellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "darkblue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "darkmagenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "darkcyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "darkgrey" | "darkgray" => Color::DarkGrey,
+        _ => return None,
+    })
+}
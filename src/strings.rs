@@ -0,0 +1,58 @@
+use crate::config::{self, Config};
+
+// Text shown by the UI, gathered here so the [strings] section of the
+// config file can reword or translate it, e.g. `prompt = "Suche: "`.
+// Messages on stderr and in --query/--serve output stay as they are.
+#[derive(Debug, Clone)]
+pub struct Strings {
+    // Ahead of the query
+    pub prompt: String,
+    // In place of the results when the query doesn't compile
+    pub invalid_regex: String,
+    // In the middle of the list when a search finds nothing
+    pub no_matches: String,
+    // At the end of the prompt row when --timeout-ms cut a search short
+    pub partial: String,
+    // At the end of the prompt row after Esc stops a search
+    pub cancelled: String,
+    // Ahead of the file name typed after Ctrl-S
+    pub export_prompt: String,
+    // Keys listed in the Ctrl-O directory browser's header
+    pub picker_help: String,
+}
+
+impl Default for Strings {
+    fn default() -> Strings {
+        Strings {
+            prompt: "Search: ".to_string(),
+            invalid_regex: "Invalid regex pattern".to_string(),
+            no_matches: "No matches".to_string(),
+            partial: "[partial]".to_string(),
+            cancelled: "cancelled, Esc to quit".to_string(),
+            export_prompt: "Export to (.json, .csv, .md, else plain): ".to_string(),
+            picker_help: "Enter: search here  Right: open  Left: parent  Esc: cancel".to_string(),
+        }
+    }
+}
+
+const NAMES: &str = "prompt, invalid_regex, no_matches, partial, cancelled, export_prompt, picker_help";
+
+// The defaults with the config file's [strings] entries applied. A value
+// may be quoted to keep spaces at its ends.
+pub fn from_config(config: &Config) -> Result<Strings, String> {
+    let mut strings = Strings::default();
+    for (name, value) in &config.strings {
+        let slot = match name.as_str() {
+            "prompt" => &mut strings.prompt,
+            "invalid_regex" => &mut strings.invalid_regex,
+            "no_matches" => &mut strings.no_matches,
+            "partial" => &mut strings.partial,
+            "cancelled" => &mut strings.cancelled,
+            "export_prompt" => &mut strings.export_prompt,
+            "picker_help" => &mut strings.picker_help,
+            _ => return Err(format!("[strings] {}: unknown name, expected one of {}", name, NAMES)),
+        };
+        *slot = config::unquote(value).to_string();
+    }
+    Ok(strings)
+}
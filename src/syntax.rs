@@ -0,0 +1,217 @@
+use crossterm::style::Color;
+
+// A small line-at-a-time highlighter for the preview pane. It knows line
+// comments, quoted strings, numbers and keywords for the built-in text
+// extensions; block comments and other multi-line constructs are not
+// tracked across lines. `regions` finds the comments and strings of a
+// whole file for --in-comments and --in-strings, block comments included.
+struct Language {
+    extensions: &'static [&'static str],
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    keywords: &'static [&'static str],
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        extensions: &["rs"],
+        line_comment: "//",
+        block_comment: Some(("/*", "*/")),
+        keywords: &[
+            "as", "break", "const", "continue", "crate", "else", "enum", "fn", "for", "if", "impl", "in",
+            "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static",
+            "struct", "super", "trait", "type", "unsafe", "use", "where", "while", "true", "false",
+        ],
+    },
+    Language {
+        extensions: &["c", "h", "cpp", "java", "go", "js", "ts", "php"],
+        line_comment: "//",
+        block_comment: Some(("/*", "*/")),
+        keywords: &[
+            "break", "case", "class", "const", "continue", "default", "do", "else", "enum", "extends",
+            "for", "func", "function", "if", "import", "interface", "let", "new", "package", "private",
+            "protected", "public", "return", "static", "struct", "switch", "this", "typedef", "var",
+            "void", "while", "true", "false", "null",
+        ],
+    },
+    Language {
+        extensions: &["py"],
+        line_comment: "#",
+        block_comment: None,
+        keywords: &[
+            "and", "as", "class", "def", "elif", "else", "except", "for", "from", "if", "import", "in",
+            "is", "lambda", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+            "True", "False", "None",
+        ],
+    },
+    Language {
+        extensions: &["sh", "bash"],
+        line_comment: "#",
+        block_comment: None,
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+            "local", "return", "then", "while",
+        ],
+    },
+    Language {
+        extensions: &["rb"],
+        line_comment: "#",
+        block_comment: None,
+        keywords: &[
+            "class", "def", "do", "else", "elsif", "end", "if", "module", "nil", "return", "self",
+            "unless", "while", "true", "false",
+        ],
+    },
+    Language {
+        extensions: &["yaml", "yml", "toml", "ini"],
+        line_comment: "#",
+        block_comment: None,
+        keywords: &["true", "false", "null"],
+    },
+    Language {
+        extensions: &["sql"],
+        line_comment: "--",
+        block_comment: Some(("/*", "*/")),
+        keywords: &[
+            "select", "from", "where", "insert", "into", "update", "delete", "create", "table", "join",
+            "on", "and", "or", "not", "null", "values", "set", "order", "by", "group",
+        ],
+    },
+];
+
+const COMMENT_COLOR: Color = Color::DarkGrey;
+const STRING_COLOR: Color = Color::Green;
+const NUMBER_COLOR: Color = Color::DarkYellow;
+const KEYWORD_COLOR: Color = Color::Blue;
+const TEXT_COLOR: Color = Color::Reset;
+
+// Split `line` into colored segments according to the language of `ext`.
+// Unknown extensions come back as a single uncolored segment.
+pub fn highlight<'a>(line: &'a str, ext: &str) -> Vec<(Color, &'a str)> {
+    let Some(language) = LANGUAGES.iter().find(|l| l.extensions.contains(&ext)) else {
+        return vec![(TEXT_COLOR, line)];
+    };
+
+    let mut segments = Vec::new();
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let c = rest.chars().next().unwrap_or(' ');
+        let token = if rest.starts_with(language.line_comment) {
+            Some((COMMENT_COLOR, line.len()))
+        } else if c == '"' || (c == '\'' && !(ext == "rs" && is_lifetime(rest))) {
+            Some((STRING_COLOR, i + string_len(rest, c)))
+        } else if c.is_ascii_digit() && !previous_is_word(line, i) {
+            let len = rest.find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '.' && ch != '_').unwrap_or(rest.len());
+            Some((NUMBER_COLOR, i + len))
+        } else if is_word_start(c) && !previous_is_word(line, i) {
+            let len = rest.find(|ch: char| !is_word_char(ch)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let keyword = if ext == "sql" {
+                language.keywords.iter().any(|k| k.eq_ignore_ascii_case(word))
+            } else {
+                language.keywords.contains(&word)
+            };
+            if keyword {
+                Some((KEYWORD_COLOR, i + len))
+            } else {
+                i += len;
+                continue;
+            }
+        } else {
+            None
+        };
+
+        match token {
+            Some((color, end)) => {
+                if plain_start < i {
+                    segments.push((TEXT_COLOR, &line[plain_start..i]));
+                }
+                segments.push((color, &line[i..end]));
+                plain_start = end;
+                i = end;
+            }
+            None => i += c.len_utf8(),
+        }
+    }
+    if plain_start < line.len() {
+        segments.push((TEXT_COLOR, &line[plain_start..]));
+    }
+    segments
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Comment,
+    String,
+}
+
+// Byte ranges of the comments and string literals in `content`, in order,
+// for the language of `ext`. None for unknown extensions. Block comments
+// may span lines; strings end at the end of their line like in highlight.
+pub fn regions(content: &str, ext: &str) -> Option<Vec<(usize, usize, Region)>> {
+    let language = LANGUAGES.iter().find(|l| l.extensions.contains(&ext))?;
+    let mut regions = Vec::new();
+    let mut i = 0;
+    while i < content.len() {
+        let rest = &content[i..];
+        let line_rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        let c = rest.chars().next().unwrap_or(' ');
+        let region = if rest.starts_with(language.line_comment) {
+            Some((Region::Comment, i + line_rest.len()))
+        } else if let Some((open, close)) = language.block_comment
+            && rest.starts_with(open)
+        {
+            let end = rest[open.len()..].find(close).map_or(content.len(), |at| i + open.len() + at + close.len());
+            Some((Region::Comment, end))
+        } else if c == '"' || (c == '\'' && !(ext == "rs" && is_lifetime(rest)) && !previous_is_word(content, i)) {
+            // A quote right after a word is an apostrophe (`don't`)
+            Some((Region::String, i + string_len(line_rest, c)))
+        } else {
+            None
+        };
+        match region {
+            Some((region, end)) => {
+                regions.push((i, end, region));
+                i = end;
+            }
+            None => i += c.len_utf8(),
+        }
+    }
+    Some(regions)
+}
+
+// Length of a string literal starting with `quote`, honoring backslash
+// escapes and running to the end of the line if it isn't closed
+fn string_len(rest: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+    rest.len()
+}
+
+// `'a` in Rust is a lifetime unless it closes like the char literal `'a'`
+fn is_lifetime(rest: &str) -> bool {
+    let mut chars = rest.chars().skip(1);
+    matches!((chars.next(), chars.next()), (Some(c), next) if is_word_start(c) && next != Some('\''))
+}
+
+fn is_word_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn previous_is_word(line: &str, i: usize) -> bool {
+    line[..i].chars().next_back().is_some_and(is_word_char)
+}
@@ -0,0 +1,48 @@
+use crate::config::Config;
+use crate::severity;
+use crossterm::style::Color;
+
+// Behind the selected result's line in the preview, unless [colors] sets
+// `preview_focus` to another color or to `none`
+const PREVIEW_FOCUS: Color = Color::DarkGrey;
+
+// Entries naming an extension, like `ext.rs = red`, color its paths
+const EXT_PREFIX: &str = "ext.";
+
+const NAMES: &str = "preview_focus, ext.EXTENSION";
+
+// The preview's focus line background from the config file's [colors]
+// section, where colors are named as in [severity]
+pub fn preview_focus(config: &Config) -> Result<Option<Color>, String> {
+    let mut focus = Some(PREVIEW_FOCUS);
+    for (name, value) in &config.colors {
+        match name.as_str() {
+            "preview_focus" if value == "none" => focus = None,
+            "preview_focus" => focus = Some(parse(name, value)?),
+            _ if name.starts_with(EXT_PREFIX) => {}
+            _ => return Err(format!("[colors] {}: unknown name, expected one of {}", name, NAMES)),
+        }
+    }
+    Ok(focus)
+}
+
+// Path colors by extension from [colors] `ext.EXTENSION = COLOR` entries,
+// checked before the built-in table; extensions are compared lowercased
+pub fn ext_colors(config: &Config) -> Result<Vec<(String, Color)>, String> {
+    let mut colors: Vec<(String, Color)> = Vec::new();
+    for (name, value) in &config.colors {
+        let Some(ext) = name.strip_prefix(EXT_PREFIX) else { continue };
+        if ext.is_empty() {
+            return Err(format!("[colors] {}: expected an extension after `{}`", name, EXT_PREFIX));
+        }
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        let color = parse(name, value)?;
+        colors.retain(|(known, _)| *known != ext);
+        colors.push((ext, color));
+    }
+    Ok(colors)
+}
+
+fn parse(name: &str, value: &str) -> Result<Color, String> {
+    severity::color(value).ok_or_else(|| format!("[colors] {}: unknown color `{}`", name, value))
+}
@@ -0,0 +1,150 @@
+use crate::search::SearchResult;
+use std::collections::HashSet;
+
+// --tree: results grouped under their directories and files, each level
+// showing how many matches it holds, flattened into rows drawn with
+// box-drawing connectors. Directories and files can be folded; the fold
+// state is kept by path so it survives a new search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    pub results: Vec<SearchResult>,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    // Connectors drawn before the row, e.g. "│  ├─ "
+    pub prefix: String,
+    pub depth: usize,
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    // A directory or file. `header` stands in for it where a result is
+    // needed: a file row opens the file, a directory row (empty path) is
+    // skipped like a listing heading.
+    Node { key: String, name: String, count: usize, folded: bool, header: Box<SearchResult> },
+    // Index into `results`
+    Match(usize),
+}
+
+// A node of the tree while it is being built, children in the order their
+// first result came
+struct Node {
+    key: String,
+    name: String,
+    children: Vec<Node>,
+    // A file's matches, as indexes into the results
+    matches: Vec<usize>,
+    is_file: bool,
+}
+
+impl Tree {
+    pub fn new(results: Vec<SearchResult>, folded: &HashSet<String>) -> Tree {
+        let mut tree = Tree { results, rows: Vec::new() };
+        tree.rebuild(folded);
+        tree
+    }
+
+    pub fn rebuild(&mut self, folded: &HashSet<String>) {
+        let mut root = Node { key: String::new(), name: String::new(), children: Vec::new(), matches: Vec::new(), is_file: false };
+        for (index, result) in self.results.iter().enumerate() {
+            if result.path.is_empty() {
+                continue;
+            }
+            let path = result.path.strip_prefix("./").unwrap_or(&result.path);
+            let mut node = &mut root;
+            let mut key = String::new();
+            let mut parts = path.split('/').filter(|part| !part.is_empty()).peekable();
+            while let Some(part) = parts.next() {
+                let is_file = parts.peek().is_none();
+                if is_file {
+                    key = result.path.clone();
+                } else {
+                    key.push_str(part);
+                    key.push('/');
+                }
+                let at = match node.children.iter().position(|child| child.key == key) {
+                    Some(at) => at,
+                    None => {
+                        node.children.push(Node {
+                            key: key.clone(),
+                            name: if is_file { part.to_string() } else { format!("{}/", part) },
+                            children: Vec::new(),
+                            matches: Vec::new(),
+                            is_file,
+                        });
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[at];
+            }
+            // Rows that only name a file (line 0) add the file without a match
+            if result.line > 0 {
+                node.matches.push(index);
+            }
+        }
+        self.rows.clear();
+        flatten(&root.children, "", 0, folded, &mut self.rows);
+    }
+
+    // Left folds the node at `index`; on a folded node or a match it moves
+    // to the parent instead. Right unfolds. Folding only adds or removes rows
+    // below the node, so it keeps its index. Returns the row to select.
+    pub fn fold(&mut self, index: usize, fold: bool, folded: &mut HashSet<String>) -> usize {
+        let Some(row) = self.rows.get(index) else {
+            return index;
+        };
+        match &row.kind {
+            Kind::Node { key, folded: is_folded, .. } if fold != *is_folded => {
+                if fold {
+                    folded.insert(key.clone());
+                } else {
+                    folded.remove(key);
+                }
+                self.rebuild(folded);
+                index
+            }
+            _ if fold => self.parent(index),
+            _ => index,
+        }
+    }
+
+    // The nearest row above `index` one level up, or `index` at the top level
+    fn parent(&self, index: usize) -> usize {
+        let depth = self.rows[index].depth;
+        (0..index).rev().find(|&at| self.rows[at].depth < depth).unwrap_or(index)
+    }
+}
+
+fn flatten(nodes: &[Node], prefix: &str, depth: usize, folded: &HashSet<String>, rows: &mut Vec<Row>) {
+    for (index, node) in nodes.iter().enumerate() {
+        let last = index + 1 == nodes.len();
+        let is_folded = folded.contains(&node.key);
+        rows.push(Row {
+            prefix: format!("{}{}", prefix, if last { "└─ " } else { "├─ " }),
+            depth,
+            kind: Kind::Node {
+                key: node.key.clone(),
+                name: node.name.clone(),
+                count: count(node),
+                folded: is_folded,
+                header: Box::new(SearchResult::file(if node.is_file { &node.key } else { "" })),
+            },
+        });
+        if is_folded {
+            continue;
+        }
+        let inner = format!("{}{}", prefix, if last { "   " } else { "│  " });
+        for (at, &result) in node.matches.iter().enumerate() {
+            let connector = if at + 1 == node.matches.len() { "└─ " } else { "├─ " };
+            rows.push(Row { prefix: format!("{}{}", inner, connector), depth: depth + 1, kind: Kind::Match(result) });
+        }
+        flatten(&node.children, &inner, depth + 1, folded, rows);
+    }
+}
+
+fn count(node: &Node) -> usize {
+    node.matches.len() + node.children.iter().map(count).sum::<usize>()
+}
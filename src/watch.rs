@@ -0,0 +1,74 @@
+use crate::archive;
+use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+// --watch: notices when searched files change on disk by comparing their
+// modification time and size every SCAN_INTERVAL. A burst of writes (a
+// build, a log being appended to) is reported once it has been quiet for
+// QUIET_PERIOD, so the search re-runs once rather than on every write.
+pub struct Watcher {
+    // Last seen (mtime, size) of each file on disk, None if unreadable
+    stamps: HashMap<String, Option<(SystemTime, u64)>>,
+    last_scan: Instant,
+    // Files changed since the last report, and when the latest change was seen
+    changed: Vec<String>,
+    last_change: Option<Instant>,
+}
+
+const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+const QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+impl Watcher {
+    pub fn new(files: &[String]) -> Watcher {
+        let mut watcher = Watcher {
+            stamps: HashMap::new(),
+            last_scan: Instant::now(),
+            changed: Vec::new(),
+            last_change: None,
+        };
+        for file in files {
+            let path = on_disk(file);
+            watcher.stamps.insert(path.to_string(), stamp(path));
+        }
+        watcher
+    }
+
+    // The files (as paths on disk) that changed, once changes have settled.
+    // Files not seen before, e.g. after a reload, start being watched.
+    pub fn poll(&mut self, files: &[String]) -> Vec<String> {
+        if self.last_scan.elapsed() >= SCAN_INTERVAL {
+            self.last_scan = Instant::now();
+            for file in files {
+                let path = on_disk(file);
+                let now = stamp(path);
+                match self.stamps.insert(path.to_string(), now) {
+                    Some(before) if before != now => {
+                        if !self.changed.iter().any(|changed| changed == path) {
+                            self.changed.push(path.to_string());
+                        }
+                        self.last_change = Some(Instant::now());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        match self.last_change {
+            Some(at) if at.elapsed() >= QUIET_PERIOD => {
+                self.last_change = None;
+                std::mem::take(&mut self.changed)
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Entries of an archive change with the archive itself
+pub fn on_disk(file: &str) -> &str {
+    archive::split(file).map_or(file, |(archive, _)| archive)
+}
+
+fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
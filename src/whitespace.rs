@@ -0,0 +1,86 @@
+use crossterm::{
+    style::{Color, Print, SetBackgroundColor},
+    ExecutableCommand,
+};
+use std::io::{self, Stdout};
+
+// Background behind whitespace at the end of a line
+const TRAILING_BACKGROUND: Color = Color::DarkRed;
+
+// The symbol drawn in place of a character that would otherwise be
+// invisible or take an unpredictable width
+fn symbol(c: char) -> Option<char> {
+    match c {
+        '\t' => Some('→'),
+        '\r' => Some('␍'),
+        '\u{a0}' => Some('⍽'),
+        '\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{feff}' => Some('¦'),
+        _ => None,
+    }
+}
+
+pub fn visible(text: &str) -> String {
+    text.chars().map(|c| symbol(c).unwrap_or(c)).collect()
+}
+
+// Print `text` in the current foreground color with invisibles replaced by
+// symbols, on TRAILING_BACKGROUND when `trailing`
+pub fn print(stdout: &mut Stdout, text: &str, trailing: bool) -> io::Result<()> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    let shown = visible(text);
+    if trailing {
+        stdout
+            .execute(SetBackgroundColor(TRAILING_BACKGROUND))?
+            .execute(Print(shown))?
+            .execute(SetBackgroundColor(Color::Reset))?;
+    } else {
+        stdout.execute(Print(shown))?;
+    }
+    Ok(())
+}
+
+// --collapse-whitespace: a line with each run of spaces and tabs shown as
+// one space
+pub struct Collapsed {
+    pub text: String,
+    // For each byte of the line and the end, where it starts in `text`; all
+    // of a run starts at its space
+    starts: Vec<usize>,
+    // For each byte of the line and the end, the length of `text` up to it
+    // (a run cut short still counts its space)
+    ends: Vec<usize>,
+}
+
+impl Collapsed {
+    // The range of `text` showing `line[start..end]`
+    pub fn range(&self, start: usize, end: usize) -> (usize, usize) {
+        (self.starts[start], self.ends[end])
+    }
+}
+
+pub fn collapse(line: &str) -> Collapsed {
+    let mut collapsed = Collapsed {
+        text: String::with_capacity(line.len()),
+        starts: Vec::with_capacity(line.len() + 1),
+        ends: Vec::with_capacity(line.len() + 1),
+    };
+    let mut in_run = false;
+    for c in line.chars() {
+        let blank = c == ' ' || c == '\t';
+        let len = collapsed.text.len();
+        // Bytes inside a char aren't asked for and share its position
+        for _ in 0..c.len_utf8() {
+            collapsed.starts.push(if blank && in_run { len - 1 } else { len });
+            collapsed.ends.push(len);
+        }
+        if !(blank && in_run) {
+            collapsed.text.push(if blank { ' ' } else { c });
+        }
+        in_run = blank;
+    }
+    collapsed.starts.push(collapsed.text.len());
+    collapsed.ends.push(collapsed.text.len());
+    collapsed
+}
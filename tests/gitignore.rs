@@ -0,0 +1,54 @@
+// --gitignore against a real repository: a global excludes file, the
+// repository's .git/info/exclude and a .gitignore, in git's precedence
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git").arg("-C").arg(dir).args(args).env("GIT_CONFIG_NOSYSTEM", "1").status().unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("termiscope-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn global_excludes_file_drops_files_from_the_walk() {
+    let home = scratch("gitignore-home");
+    let repo = home.join("repo");
+    fs::create_dir_all(repo.join("données")).unwrap();
+    git(&repo, &["init", "-q"]);
+
+    // core.excludesFile comes from the global config, which points at it
+    let global_config = home.join("gitconfig");
+    let excludes = home.join("ignore");
+    fs::write(&global_config, format!("[core]\n\texcludesFile = {}\n", excludes.display())).unwrap();
+    fs::write(&excludes, "*.log\ndonnées/\n").unwrap();
+    fs::write(repo.join(".git/info/exclude"), "*.tmp\n").unwrap();
+    // A .gitignore takes precedence over both and can re-include
+    fs::write(repo.join(".gitignore"), "!kept.log\n").unwrap();
+
+    for file in ["main.txt", "debug.log", "kept.log", "scratch.tmp", "données/été.txt"] {
+        fs::write(repo.join(file), "needle\n").unwrap();
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_termiscope"))
+        .args(["--gitignore", "--add-extensions", "log,tmp", "--list-files"])
+        .current_dir(&repo)
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("GIT_CONFIG_GLOBAL", &global_config)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut listed: Vec<String> = String::from_utf8(output.stdout).unwrap().lines().map(str::to_string).collect();
+    listed.sort();
+    assert_eq!(listed, ["kept.log", "main.txt"]);
+
+    fs::remove_dir_all(&home).unwrap();
+}